@@ -1,3 +1,33 @@
 fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!(
+        "cargo:rustc-env=MARVELMIND_VERSION={}",
+        marvelmind_version().unwrap_or_else(|| "unknown".into())
+    );
+
     tauri_build::build()
 }
+
+/// Reads the locked version of the marvelmind crate, along with the commit it
+/// was built from since it comes from git.
+fn marvelmind_version() -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let mut lines = lock
+        .lines()
+        .skip_while(|line| *line != "name = \"marvelmind\"")
+        .skip(1);
+
+    let version = lines
+        .next()?
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')?;
+    let commit = lines
+        .next()
+        .and_then(|line| line.rsplit_once('#'))
+        .map(|(_, commit)| commit.trim_end_matches('"'));
+
+    Some(match commit {
+        Some(commit) => format!("{} ({})", version, &commit[..commit.len().min(7)]),
+        None => version.to_string(),
+    })
+}
@@ -0,0 +1,699 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Error, Result};
+use tauri::AppHandle;
+
+use crate::{
+    record::{
+        csv_reader, read_comments, read_delimiter, read_samples, sample_reader, warn_read_issues,
+        Comment, Sample,
+    },
+    send_error,
+};
+
+// approximate length of one degree of latitude, used to place local
+// coordinates into a synthetic frame around (0, 0)
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+fn read_recording(app: &AppHandle, path: &Path) -> Result<Vec<Sample>, Error> {
+    let samples = read_samples(path)?;
+    samples.warn(app, path);
+
+    Ok(samples.samples)
+}
+
+fn write_gpx(samples: Vec<Sample>, path: &Path) -> Result<(), Error> {
+    // beacons are stationary, so only hedgehog rows make tracks
+    let mut tracks = BTreeMap::<u8, Vec<Sample>>::new();
+    for sample in samples.into_iter().filter(|sample| sample.is_hedge) {
+        tracks.entry(sample.address).or_default().push(sample);
+    }
+
+    let file = File::create(path).context(format!("failed to create file: {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="mmvisual" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+
+    for (address, mut track) in tracks {
+        track.sort_by_key(|s| s.t);
+
+        writeln!(writer, "  <trk>")?;
+        writeln!(writer, "    <name>hedgehog {}</name>", address)?;
+        writeln!(writer, "    <trkseg>")?;
+
+        for sample in track {
+            let time = chrono::DateTime::from_timestamp_millis(sample.t as i64)
+                .context(format!("invalid timestamp: {}", sample.t))?;
+
+            writeln!(
+                writer,
+                r#"      <trkpt lat="{:.9}" lon="{:.9}">"#,
+                sample.y / METERS_PER_DEGREE,
+                sample.x / METERS_PER_DEGREE
+            )?;
+            writeln!(writer, "        <ele>{}</ele>", sample.z)?;
+            writeln!(
+                writer,
+                "        <time>{}</time>",
+                time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+            )?;
+            writeln!(writer, "      </trkpt>")?;
+        }
+
+        writeln!(writer, "    </trkseg>")?;
+        writeln!(writer, "  </trk>")?;
+    }
+
+    writeln!(writer, "</gpx>")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes a `#` comment row after the records written so far.
+fn write_comment(writer: &mut csv::Writer<BufWriter<File>>, text: &str) -> Result<(), Error> {
+    // the csv writer only lends out the file, so write past it once flushed
+    writer.flush()?;
+    writeln!(writer.get_ref().get_ref(), "{}", text)?;
+
+    Ok(())
+}
+
+fn write_json(app: &AppHandle, in_path: &Path, out_path: &Path) -> Result<(), Error> {
+    let mut samples = sample_reader(in_path)?;
+
+    let file =
+        File::create(out_path).context(format!("failed to create file: {}", out_path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut written = 0;
+    let mut skipped = 0;
+
+    writer.write_all(b"[")?;
+    for sample in &mut samples {
+        let Some(sample) = sample? else {
+            skipped += 1;
+            continue;
+        };
+
+        if written > 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\n  ")?;
+        serde_json::to_writer(&mut writer, &sample)?;
+        written += 1;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()?;
+
+    warn_read_issues(app, in_path, skipped, samples.unknown_columns());
+
+    Ok(())
+}
+
+fn decimate(in_path: &Path, out_path: &Path, factor: usize) -> Result<(), Error> {
+    if factor == 0 {
+        return Err(Error::msg("factor must be at least 1"));
+    }
+
+    let mut reader = csv_reader(in_path)?;
+    let header = reader.headers()?.clone();
+    let column = header
+        .iter()
+        .position(|name| name == "address")
+        .context("no column: address")?;
+
+    let address = |record: &csv::StringRecord| record.get(column)?.parse::<u8>().ok();
+
+    // count rows first so the last sample of each device can be kept
+    let mut counts = HashMap::<u8, usize>::new();
+    for record in reader.records() {
+        if let Some(address) = address(&record?) {
+            *counts.entry(address).or_default() += 1;
+        }
+    }
+
+    let file =
+        File::create(out_path).context(format!("failed to create file: {}", out_path.display()))?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(read_delimiter(in_path)?)
+        .flexible(true)
+        .from_writer(BufWriter::new(file));
+    writer.write_record(&header)?;
+
+    // comment rows keep their place among the kept rows
+    let mut comments = read_comments(in_path)?.into_iter().peekable();
+
+    let mut indices = HashMap::<u8, usize>::new();
+    for (row, record) in csv_reader(in_path)?.records().enumerate() {
+        let record = record?;
+        while let Some(comment) = comments.next_if(|comment| comment.row <= row) {
+            write_comment(&mut writer, &comment.text)?;
+        }
+
+        let Some(address) = address(&record) else {
+            continue;
+        };
+
+        let index = indices.entry(address).or_default();
+        if index.is_multiple_of(factor) || *index + 1 == counts[&address] {
+            writer.write_record(&record)?;
+        }
+        *index += 1;
+    }
+
+    for comment in comments {
+        write_comment(&mut writer, &comment.text)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// How `split_recording` divides a recording.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) enum SplitMode {
+    /// One file per address.
+    ByAddress,
+    /// A new file whenever no row arrives for longer than this many
+    /// milliseconds.
+    ByGap(u64),
+}
+
+/// An output file of `split`, the clock its rows were last marked with and
+/// the times its rows span.
+struct Part {
+    writer: csv::Writer<BufWriter<File>>,
+    time_source: Option<String>,
+    span: Option<(u64, u64)>,
+}
+
+impl Part {
+    /// How far `t` falls outside the times of this part's rows.
+    fn distance(&self, t: u64) -> u64 {
+        self.span.map_or(u64::MAX, |(first, last)| {
+            first.saturating_sub(t).max(t.saturating_sub(last))
+        })
+    }
+}
+
+fn split(in_path: &Path, mode: SplitMode, out_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut reader = csv_reader(in_path)?;
+    let header = reader.headers()?.clone();
+    let column = |name: &str| header.iter().position(|column| column == name);
+
+    let address_column = column("address").context("no column: address")?;
+    let t_column = column("t");
+    if let (SplitMode::ByGap(_), None) = (mode, t_column) {
+        return Err(Error::msg("no column: t"));
+    }
+
+    std::fs::create_dir_all(out_dir)
+        .context(format!("failed to create directory: {}", out_dir.display()))?;
+
+    let stem = in_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("recording");
+    let delimiter = read_delimiter(in_path)?;
+
+    let mut parts = HashMap::<String, Part>::new();
+    let mut paths = Vec::<String>::new();
+    let mut segment = 1;
+    let mut last_t: Option<u64> = None;
+
+    // the clock marker in effect is repeated in every part it applies to,
+    // comment rows with a time go to the parts covering it once every row is
+    // written, and other comment rows go to the part of the row after them
+    let mut comments = read_comments(in_path)?.into_iter().peekable();
+    let mut time_source: Option<String> = None;
+    let mut timed = Vec::<(u64, String)>::new();
+    let mut pending = Vec::<String>::new();
+    let mut last_part: Option<String> = None;
+
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        while let Some(comment) = comments.next_if(|comment| comment.row <= row) {
+            match (comment.is_time_source(), comment.t()) {
+                (true, _) => time_source = Some(comment.text),
+                (false, Some(t)) => timed.push((t, comment.text)),
+                (false, None) => pending.push(comment.text),
+            }
+        }
+
+        let Some(address) = record
+            .get(address_column)
+            .and_then(|value| value.parse::<u8>().ok())
+        else {
+            continue;
+        };
+
+        let t = t_column
+            .and_then(|column| record.get(column))
+            .and_then(|value| value.parse::<u64>().ok());
+
+        let part = match mode {
+            SplitMode::ByAddress => address.to_string(),
+            SplitMode::ByGap(gap_ms) => {
+                let Some(t) = t else {
+                    continue;
+                };
+
+                if last_t.is_some_and(|last_t| t.saturating_sub(last_t) > gap_ms) {
+                    segment += 1;
+                }
+                last_t = Some(last_t.map_or(t, |last_t| last_t.max(t)));
+
+                segment.to_string()
+            }
+        };
+
+        last_part = Some(part.clone());
+        let part = match parts.entry(part) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = out_dir.join(format!("{}-{}.csv", stem, entry.key()));
+                let file = File::create(&path)
+                    .context(format!("failed to create file: {}", path.display()))?;
+
+                let mut writer = csv::WriterBuilder::new()
+                    .delimiter(delimiter)
+                    .flexible(true)
+                    .from_writer(BufWriter::new(file));
+                writer.write_record(&header)?;
+
+                paths.push(path.to_string_lossy().into());
+                entry.insert(Part {
+                    writer,
+                    time_source: None,
+                    span: None,
+                })
+            }
+        };
+
+        if let Some(text) = &time_source {
+            if part.time_source.as_ref() != Some(text) {
+                write_comment(&mut part.writer, text)?;
+                part.time_source = Some(text.clone());
+            }
+        }
+        for text in pending.drain(..) {
+            write_comment(&mut part.writer, &text)?;
+        }
+        part.writer.write_record(&record)?;
+        if let Some(t) = t {
+            part.span = Some(
+                part.span
+                    .map_or((t, t), |(first, last)| (first.min(t), last.max(t))),
+            );
+        }
+    }
+
+    // comment rows after the last row stay at the end of the last part
+    for comment in comments.filter(|comment| !comment.is_time_source()) {
+        match comment.t() {
+            Some(t) => timed.push((t, comment.text)),
+            None => pending.push(comment.text),
+        }
+    }
+    if let Some(part) = last_part.and_then(|last_part| parts.get_mut(&last_part)) {
+        for text in pending {
+            write_comment(&mut part.writer, &text)?;
+        }
+    }
+
+    timed.sort_by_key(|&(t, _)| t);
+    for (t, text) in timed {
+        let Some(closest) = parts.values().map(|part| part.distance(t)).min() else {
+            break;
+        };
+        for part in parts
+            .values_mut()
+            .filter(|part| part.distance(t) == closest)
+        {
+            write_comment(&mut part.writer, &text)?;
+        }
+    }
+
+    for part in parts.values_mut() {
+        part.writer.flush()?;
+    }
+
+    Ok(paths)
+}
+
+fn merge(in_paths: &[String], out_path: &Path) -> Result<(), Error> {
+    let first = in_paths.first().context("no recordings to merge")?;
+
+    let mut header = Vec::<String>::new();
+    let mut rows = Vec::<(u64, Option<usize>, Vec<String>)>::new();
+    let mut seen = HashSet::<(u8, u64)>::new();
+
+    // rows remember the clock marker they were under, and other comment rows
+    // are placed after the rows up to their own time, or else the time of the
+    // row before them
+    let mut time_sources = Vec::<String>::new();
+    let mut comments = Vec::<(u64, String)>::new();
+    let mut seen_comments = HashSet::<String>::new();
+    let mut take_comment = |comment: Comment, time_source: &mut Option<usize>, t: u64| {
+        if comment.is_time_source() {
+            let index = match time_sources.iter().position(|text| *text == comment.text) {
+                Some(index) => index,
+                None => {
+                    time_sources.push(comment.text);
+                    time_sources.len() - 1
+                }
+            };
+            *time_source = Some(index);
+        } else if seen_comments.insert(comment.text.clone()) {
+            comments.push((comment.t().unwrap_or(t), comment.text));
+        }
+    };
+
+    for in_path in in_paths {
+        let in_path = Path::new(in_path);
+        let mut reader = csv_reader(in_path)?;
+
+        // map this file's columns into the union of every file's columns
+        let mut columns = Vec::<usize>::new();
+        for name in reader.headers()?.iter() {
+            let index = match header.iter().position(|column| column == name) {
+                Some(index) => index,
+                None => {
+                    header.push(name.to_string());
+                    header.len() - 1
+                }
+            };
+            columns.push(index);
+        }
+
+        let position = |name: &str| {
+            columns
+                .iter()
+                .position(|&index| header[index] == name)
+                .context(format!("no column: {} in {}", name, in_path.display()))
+        };
+        let address_column = position("address")?;
+        let t_column = position("t")?;
+
+        let mut file_comments = read_comments(in_path)?.into_iter().peekable();
+        let mut time_source: Option<usize> = None;
+        let mut last_t = 0;
+
+        for (index, record) in reader.records().enumerate() {
+            let record = record?;
+            while let Some(comment) = file_comments.next_if(|comment| comment.row <= index) {
+                take_comment(comment, &mut time_source, last_t);
+            }
+
+            let (Some(address), Some(t)) = (
+                record
+                    .get(address_column)
+                    .and_then(|v| v.parse::<u8>().ok()),
+                record.get(t_column).and_then(|v| v.parse::<u64>().ok()),
+            ) else {
+                continue;
+            };
+            last_t = t;
+
+            // overlapping recordings of the same session repeat rows
+            if !seen.insert((address, t)) {
+                continue;
+            }
+
+            let mut row = Vec::<String>::new();
+            for (value, &index) in record.iter().zip(&columns) {
+                if row.len() <= index {
+                    row.resize(index + 1, String::new());
+                }
+                row[index] = value.to_string();
+            }
+            rows.push((t, time_source, row));
+        }
+
+        for comment in file_comments {
+            take_comment(comment, &mut time_source, last_t);
+        }
+    }
+
+    rows.sort_by_key(|&(t, _, _)| t);
+    comments.sort_by_key(|&(t, _)| t);
+
+    let file =
+        File::create(out_path).context(format!("failed to create file: {}", out_path.display()))?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(read_delimiter(Path::new(first))?)
+        .from_writer(BufWriter::new(file));
+    writer.write_record(&header)?;
+
+    let mut comments = comments.into_iter().peekable();
+    let mut written_source: Option<usize> = None;
+    for (t, time_source, mut row) in rows {
+        while let Some((_, text)) = comments.next_if(|&(comment_t, _)| comment_t < t) {
+            write_comment(&mut writer, &text)?;
+        }
+        if let Some(index) = time_source.filter(|&index| written_source != Some(index)) {
+            write_comment(&mut writer, &time_sources[index])?;
+            written_source = Some(index);
+        }
+
+        row.resize(header.len(), String::new());
+        writer.write_record(&row)?;
+    }
+    for (_, text) in comments {
+        write_comment(&mut writer, &text)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the hedgehog rows of a recording as one GPX track per hedgehog.
+#[tauri::command]
+pub(crate) fn export_gpx(app: AppHandle, in_path: String, out_path: String) -> Result<(), String> {
+    read_recording(&app, Path::new(&in_path))
+        .and_then(|samples| write_gpx(samples, Path::new(&out_path)))
+        .map_err(|err| {
+            let msg = format!("failed to export gpx: {:#}", err);
+            send_error(app.clone(), msg.clone());
+            msg
+        })
+}
+
+#[tauri::command]
+pub(crate) fn export_json(app: AppHandle, in_path: String, out_path: String) -> Result<(), String> {
+    write_json(&app, Path::new(&in_path), Path::new(&out_path)).map_err(|err| {
+        let msg = format!("failed to export json: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
+
+/// Writes a copy of a recording keeping every `factor`-th row of each
+/// device, along with each device's first and last row. Comment rows are
+/// all kept.
+#[tauri::command]
+pub(crate) fn decimate_log(
+    app: AppHandle,
+    in_path: String,
+    out_path: String,
+    factor: usize,
+) -> Result<(), String> {
+    decimate(Path::new(&in_path), Path::new(&out_path), factor).map_err(|err| {
+        let msg = format!("failed to decimate recording: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
+
+/// Splits a recording into files in `out_dir`, named after the recording and
+/// the address or segment number, and returns their paths. Annotations go to
+/// the files whose rows span their time, or the closest ones.
+#[tauri::command]
+pub(crate) fn split_recording(
+    app: AppHandle,
+    in_path: String,
+    mode: SplitMode,
+    out_dir: String,
+) -> Result<Vec<String>, String> {
+    split(Path::new(&in_path), mode, Path::new(&out_dir)).map_err(|err| {
+        let msg = format!("failed to split recording: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
+
+/// Writes the rows of several recordings to `out_path` ordered by time,
+/// under the union of their columns. Rows repeating an address and time
+/// already merged are dropped, and so are repeated comment rows.
+#[tauri::command]
+pub(crate) fn merge_recordings(
+    app: AppHandle,
+    paths: Vec<String>,
+    out_path: String,
+) -> Result<(), String> {
+    merge(&paths, Path::new(&out_path)).map_err(|err| {
+        let msg = format!("failed to merge recordings: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("mmvisual-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into()
+    }
+
+    #[test]
+    fn gpx_has_only_hedgehog_tracks() {
+        let dir = temp_dir("gpx");
+        let sample = |address, is_hedge| Sample {
+            address,
+            t: 1000,
+            is_hedge,
+            ..Default::default()
+        };
+        let out = dir.join("tracks.gpx");
+
+        write_gpx(vec![sample(3, true), sample(5, false)], &out).unwrap();
+
+        let gpx = std::fs::read_to_string(&out).unwrap();
+        assert!(gpx.contains("<name>hedgehog 3</name>"));
+        assert!(!gpx.contains("<name>hedgehog 5</name>"));
+        assert_eq!(gpx.matches("<trkpt ").count(), 1);
+    }
+
+    #[test]
+    fn overlapping_recordings_merge_in_time_order() {
+        let dir = temp_dir("merge");
+        let first = write(&dir, "first.csv", "address,t,x\n3,10,0\n3,30,1\n5,20,2\n");
+        let second = write(&dir, "second.csv", "address,t,x\n5,20,2\n3,25,3\n3,40,4\n");
+        let out = dir.join("merged.csv");
+
+        merge(&[first, second], &out).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "address,t,x\n3,10,0\n5,20,2\n3,25,3\n3,30,1\n3,40,4\n"
+        );
+    }
+
+    #[test]
+    fn decimate_keeps_comment_rows_in_place() {
+        let dir = temp_dir("decimate-comments");
+        let input = write(
+            &dir,
+            "record.csv",
+            "address,t\n# timestamps host\n3,1\n3,2\n# annotation 2 door\n\
+             # timestamps modem\n3,3\n3,4\n3,5\n",
+        );
+        let out = dir.join("decimated.csv");
+
+        decimate(Path::new(&input), &out, 2).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "address,t\n# timestamps host\n3,1\n# annotation 2 door\n\
+             # timestamps modem\n3,3\n3,5\n"
+        );
+    }
+
+    #[test]
+    fn split_parts_are_marked_with_their_clock() {
+        let dir = temp_dir("split-comments");
+        let input = write(
+            &dir,
+            "record.csv",
+            "address,t\n# timestamps host\n3,1\n5,2\n# timestamps modem\n5,3\n3,4\n",
+        );
+
+        let paths = split(Path::new(&input), SplitMode::ByAddress, &dir.join("parts")).unwrap();
+
+        let read = |index: usize| std::fs::read_to_string(&paths[index]).unwrap();
+        assert_eq!(
+            read(0),
+            "address,t\n# timestamps host\n3,1\n# timestamps modem\n3,4\n"
+        );
+        assert_eq!(
+            read(1),
+            "address,t\n# timestamps host\n5,2\n# timestamps modem\n5,3\n"
+        );
+    }
+
+    #[test]
+    fn split_annotations_go_to_the_part_covering_them() {
+        let dir = temp_dir("split-annotations");
+        let input = write(
+            &dir,
+            "record.csv",
+            "address,t\n# timestamps host\n3,1\n3,3\n# annotation 2 door\n\
+             # timestamps modem\n3,100\n# annotation 150 late\n3,101\n",
+        );
+
+        let paths = split(Path::new(&input), SplitMode::ByGap(10), &dir.join("parts")).unwrap();
+
+        let read = |index: usize| std::fs::read_to_string(&paths[index]).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            read(0),
+            "address,t\n# timestamps host\n3,1\n3,3\n# annotation 2 door\n"
+        );
+        assert_eq!(
+            read(1),
+            "address,t\n# timestamps modem\n3,100\n3,101\n# annotation 150 late\n"
+        );
+    }
+
+    #[test]
+    fn merge_keeps_clock_markers_and_annotations() {
+        let dir = temp_dir("merge-comments");
+        let first = write(
+            &dir,
+            "first.csv",
+            "address,t\n# timestamps host\n3,10\n3,30\n# annotation 30 door\n",
+        );
+        let second = write(
+            &dir,
+            "second.csv",
+            "address,t\n# timestamps modem\n3,20\n3,30\n# annotation 30 door\n3,40\n",
+        );
+        let out = dir.join("merged.csv");
+
+        merge(&[first, second], &out).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "address,t\n# timestamps host\n3,10\n# timestamps modem\n3,20\n\
+             # timestamps host\n3,30\n# annotation 30 door\n# timestamps modem\n3,40\n"
+        );
+    }
+}
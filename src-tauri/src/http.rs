@@ -0,0 +1,163 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tauri::AppHandle;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{conn_status, display_devices, lock_state, send_error, send_log, send_warn};
+
+const DEFAULT_HTTP_PORT: u16 = 4421;
+const RECV_TIMEOUT: Duration = Duration::from_millis(100);
+const REBIND_INTERVAL: Duration = Duration::from_millis(10);
+const REBIND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A localhost HTTP server exposing `GET /devices` and `GET /status`.
+#[derive(Debug)]
+pub(crate) struct HttpServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    serve_thread: JoinHandle<()>,
+}
+
+impl HttpServer {
+    /// Stops serving and waits for the server to be dropped, so the port can
+    /// be bound again. A request in progress locks the app state, so this
+    /// must not be called with it locked.
+    fn shutdown(self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        let _ = self.serve_thread.join();
+    }
+}
+
+fn respond(app: &AppHandle, request: Request) {
+    let body = {
+        let state = lock_state(app);
+
+        match (request.method(), request.url()) {
+            (Method::Get, "/devices") => {
+                serde_json::to_string(&display_devices(&state, &state.devices)).ok()
+            }
+            (Method::Get, "/status") => serde_json::to_string(&conn_status(&state)).ok(),
+            _ => None,
+        }
+    };
+
+    let result = match body {
+        Some(body) => request.respond(
+            Response::from_string(body)
+                .with_header(Header::from_bytes("Content-Type", "application/json").unwrap()),
+        ),
+        None => request.respond(Response::from_string("not found").with_status_code(404)),
+    };
+
+    if let Err(err) = result {
+        send_warn(
+            app.clone(),
+            format!("failed to respond to http request: {}", err),
+        );
+    }
+}
+
+fn serve(app: AppHandle, server: Server, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::Relaxed) {
+        match server.recv_timeout(RECV_TIMEOUT) {
+            Ok(Some(request)) => respond(&app, request),
+            Ok(None) => continue,
+            Err(err) => {
+                send_error(app, format!("http server stopped: {}", err));
+                return;
+            }
+        }
+    }
+}
+
+/// Binds a server on `port`. The server's own accept thread lets go of the
+/// port only shortly after the server is dropped, so when `is_rebinding` the
+/// port is retried for a while.
+fn bind(port: u16, is_rebinding: bool) -> Result<Server, String> {
+    let started = Instant::now();
+
+    loop {
+        match Server::http(("127.0.0.1", port)) {
+            Ok(server) => return Ok(server),
+            Err(err)
+                if is_rebinding
+                    && started.elapsed() < REBIND_TIMEOUT
+                    && err
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(|err| err.kind() == ErrorKind::AddrInUse) =>
+            {
+                sleep(REBIND_INTERVAL);
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn start_http_server(app: AppHandle, port: Option<u16>) {
+    let port = port.unwrap_or(DEFAULT_HTTP_PORT);
+
+    let http_server = lock_state(&app).http_server.take();
+    let is_rebinding = http_server
+        .as_ref()
+        .is_some_and(|server| server.port == port);
+    if let Some(http_server) = http_server {
+        http_server.shutdown();
+    }
+
+    let mut state = lock_state(&app);
+
+    let server = match bind(port, is_rebinding) {
+        Ok(server) => server,
+        Err(err) => {
+            send_error(
+                app.clone(),
+                format!("failed to start http server on port {}: {}", port, err),
+            );
+            return;
+        }
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let serve_thread = thread::spawn({
+        let app = app.clone();
+        let stop = stop.clone();
+        move || serve(app, server, stop)
+    });
+
+    let http_server = HttpServer {
+        port,
+        stop,
+        serve_thread,
+    };
+
+    send_log(
+        app.clone(),
+        format!("http server listening on port {}", http_server.port),
+    );
+    state.http_server = Some(http_server);
+}
+
+#[tauri::command]
+pub(crate) fn stop_http_server(app: AppHandle) {
+    let http_server = lock_state(&app).http_server.take();
+    if let Some(http_server) = http_server {
+        http_server.shutdown();
+    }
+}
@@ -5,299 +5,2565 @@
 // those terms.
 
 use std::{
-    fs::File,
-    io::Write,
-    path::PathBuf,
-    sync::Mutex,
-    thread::sleep,
-    time::{Duration, SystemTime},
+    collections::{HashMap, HashSet, VecDeque},
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::mpsc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{Context, Error, Result};
+use base64::Engine;
 use ini::Ini;
 use marvelmind as mm;
-use tauri::{async_runtime::spawn, AppHandle, Emitter, Manager};
+use tauri::{AppHandle, Emitter, Manager, RunEvent};
 
-const LOG_PATH: &str = "log.csv";
+mod export;
+mod http;
+mod logger;
+mod mqtt;
+mod playback;
+mod record;
+mod settings;
+mod simulate;
+mod snapshot;
+mod stats;
+mod ws;
+mod zone;
 
-macro_rules! unwrap_or_return {
-    ( $e:expr, $app:expr ) => {
-        match $e {
-            Ok(x) => x,
-            Err(err) => {
-                send_log($app, err.to_string());
-                return;
-            }
-        }
-    };
-}
+use http::HttpServer;
+use logger::Logger;
+use mqtt::MqttPublisher;
+use record::{RecordField, RecordSummary, Recording, RecordingInfo, TimeSource};
+use settings::{save_settings, Settings};
+use stats::{LoopTimer, Throughput};
+use ws::WsServer;
+use zone::Zone;
 
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+const RECORD_NAME_FORMAT: &str = "mmvisual-%Y-%m-%dT%H-%M-%S.csv";
+const RECORDINGS_DIR: &str = "recordings";
+const DEFAULT_PORT: u32 = 5;
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_ENUMERATE_RETRIES: u32 = 3;
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_OPEN_TIMEOUT_MS: u64 = 5000;
+const ENUMERATE_INTERVAL: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+const PENDING_OPEN_INTERVAL: Duration = Duration::from_millis(50);
+const FEET_PER_METER: f64 = 1.0 / 0.3048;
+const DEFAULT_LOST_TIMEOUT_MS: u64 = 3000;
+const DEFAULT_TRAJECTORY_LIMIT: usize = 1000;
+const DEFAULT_EMIT_RATE_HZ: f64 = 30.0;
+const DEFAULT_QUALITY_ALERT: u8 = 50;
+const MAX_OUTLIER_RUN: u32 = 5;
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "svg"];
+// pixels per absolute SVG length unit
+const SVG_UNITS: [(&str, f64); 6] = [
+    ("px", 1.0),
+    ("in", 96.0),
+    ("cm", 96.0 / 2.54),
+    ("mm", 96.0 / 25.4),
+    ("pt", 96.0 / 72.0),
+    ("pc", 16.0),
+];
+const MULTI_INSTANCE_ENV: &str = "MMVISUAL_MULTI_INSTANCE";
+const MULTI_INSTANCE_FLAG: &str = "--multi-instance";
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 struct TRDevice {
     address: u8,
+    name: Option<String>,
+    color: Option<String>,
+    dtype: String,
     is_hedge: bool,
     x: f64,
     y: f64,
+    z: f64,
+    vx: f64,
+    vy: f64,
+    vz: f64,
     q: u8,
+    last_update_ms: u64,
 }
 
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+impl TRDevice {
+    /// Whether this is the modem, which the device list reports along with
+    /// the beacons and hedgehogs.
+    fn is_modem(&self) -> bool {
+        self.dtype.contains("Modem")
+    }
+
+    /// Whether this is a stationary beacon, as opposed to a hedgehog or the
+    /// modem.
+    fn is_beacon(&self) -> bool {
+        !self.is_hedge && !self.is_modem()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogLevel {
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+struct LogMessage {
+    level: LogLevel,
+    message: String,
+    timestamp: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct TRFloor {
+    file: String,
+    data: Vec<u8>,
+    ext: String,
+    /// Where `file` resolved to, so the floor can be re-exported elsewhere.
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl TRFloor {
+    fn mime_type(&self) -> &'static str {
+        match self.ext.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "bmp" => "image/bmp",
+            "svg" => "image/svg+xml",
+            _ => "application/octet-stream",
+        }
+    }
+
+    fn data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime_type(),
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 struct TRPlan {
     x: f64,
     y: f64,
     scale_pixels_per_m: f64,
-    data: Vec<u8>,
-    ext: String,
+    rotation_deg: f64,
+    floors: Vec<TRFloor>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct PortInfo {
+    name: String,
+    vid: Option<u16>,
+    pid: Option<u16>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial_number: Option<String>,
+}
+
+/// A location reported by the modem, with coordinates in meters.
+#[derive(Debug, Default, Clone, Copy)]
+struct Location {
+    address: u8,
+    is_hedge: bool,
+    x: f64,
+    y: f64,
+    z: f64,
+    q: u8,
+    t: u64,
+}
+
+#[derive(Debug, Default)]
+struct DeviceTrack {
+    prev: Option<(f64, f64, f64, u64)>,
+    window: VecDeque<(f64, f64, f64)>,
+    trajectory: VecDeque<(f64, f64, f64, u64)>,
+    is_lost: bool,
+    quality: Option<u8>,
+    outliers: u32,
+    last_t: Option<u64>,
+    zones: HashSet<String>,
+}
+
+impl DeviceTrack {
+    /// Records a new position and returns the velocity since the previous
+    /// one, or `None` if `t` is not newer than the last recorded sample.
+    fn update_velocity(&mut self, x: f64, y: f64, z: f64, t: u64) -> Option<(f64, f64, f64)> {
+        let velocity = match self.prev {
+            Some((_, _, _, prev_t)) if t <= prev_t => return None,
+            Some((prev_x, prev_y, prev_z, prev_t)) => {
+                let dt = (t - prev_t) as f64 / 1000.0;
+                ((x - prev_x) / dt, (y - prev_y) / dt, (z - prev_z) / dt)
+            }
+            None => (0.0, 0.0, 0.0),
+        };
+
+        self.prev = Some((x, y, z, t));
+
+        Some(velocity)
+    }
+
+    /// Pushes a raw position into the smoothing window, dropping the oldest
+    /// ones so at most `size` positions are kept.
+    fn push_window(&mut self, x: f64, y: f64, z: f64, size: usize) {
+        self.window.push_back((x, y, z));
+        while self.window.len() > size.max(1) {
+            self.window.pop_front();
+        }
+    }
+
+    /// Appends a position to the trajectory, keeping at most `limit` points
+    /// no older than `duration_ms` before it, where a duration of 0 keeps
+    /// points regardless of age.
+    fn push_trajectory(&mut self, point: (f64, f64, f64, u64), limit: usize, duration_ms: u64) {
+        self.trajectory.push_back(point);
+        while self.trajectory.len() > limit {
+            self.trajectory.pop_front();
+        }
+
+        if duration_ms > 0 {
+            while let Some(&(_, _, _, t)) = self.trajectory.front() {
+                if t.saturating_add(duration_ms) >= point.3 {
+                    break;
+                }
+                self.trajectory.pop_front();
+            }
+        }
+    }
+
+    /// Returns whether a position jumps more than `max_jump_m` from the
+    /// smoothed estimate, where 0 accepts every position. After
+    /// `MAX_OUTLIER_RUN` outliers in a row the device is taken to have really
+    /// moved, and the window restarts from the new position.
+    fn is_outlier(&mut self, x: f64, y: f64, z: f64, max_jump_m: f64) -> bool {
+        let Some((sx, sy, sz)) = self.smoothed() else {
+            return false;
+        };
+
+        let jump = ((x - sx).powi(2) + (y - sy).powi(2) + (z - sz).powi(2)).sqrt();
+        if max_jump_m <= 0.0 || jump <= max_jump_m {
+            self.outliers = 0;
+            return false;
+        }
+
+        self.outliers += 1;
+        if self.outliers > MAX_OUTLIER_RUN {
+            self.outliers = 0;
+            self.window.clear();
+            return false;
+        }
+
+        true
+    }
+
+    fn smoothed(&self) -> Option<(f64, f64, f64)> {
+        if self.window.is_empty() {
+            return None;
+        }
+
+        let n = self.window.len() as f64;
+        let (x, y, z) = self
+            .window
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(ax, ay, az), &(x, y, z)| {
+                (ax + x, ay + y, az + z)
+            });
+
+        Some((x / n, y / n, z / n))
+    }
+}
+
+/// Coalesces device changes so they are emitted at most at a given rate.
+#[derive(Debug, Default)]
+struct EmitThrottle {
+    last_emit: Option<Instant>,
+    is_pending: bool,
+}
+
+impl EmitThrottle {
+    fn mark(&mut self, is_changed: bool) {
+        self.is_pending |= is_changed;
+    }
+
+    /// Returns whether pending changes should be emitted now, where a rate of
+    /// 0 emits every change.
+    fn take(&mut self, hz: f64) -> bool {
+        let is_due = match self.last_emit {
+            Some(last_emit) if hz > 0.0 => last_emit.elapsed().as_secs_f64() >= 1.0 / hz,
+            _ => true,
+        };
+
+        if !self.is_pending || !is_due {
+            return false;
+        }
+
+        self.last_emit = Some(Instant::now());
+        self.is_pending = false;
+
+        true
+    }
+}
+
+/// A trajectory point in display coordinates, with its age so older points
+/// can be faded.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct TrailPoint {
+    x: f64,
+    y: f64,
+    z: f64,
+    t: u64,
+    age_ms: u64,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ConnStatus {
+    is_running: bool,
+    port: u32,
+    device_count: usize,
+    last_update_age_ms: Option<u64>,
+}
+
+/// An axis-aligned extent in display coordinates.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct Bounds {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Bounds {
+    fn point(x: f64, y: f64) -> Bounds {
+        Bounds {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+        }
+    }
+
+    fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+/// Everything the frontend needs to show a newly loaded map, emitted as
+/// `map-loaded`.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+struct MapLoaded {
+    plan: TRPlan,
+    bounds: Option<Bounds>,
+    devices: Vec<TRDevice>,
+}
+
+/// What `parse_map` returns. A map that fails to parse comes back with no
+/// devices or plan and the reason in `error`, rather than as a rejection.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+struct ParsedMap {
+    devices: Vec<TRDevice>,
+    plan: Option<TRPlan>,
+    error: Option<String>,
+}
+
+/// Requests handled by `mmrun` between polls, since the modem only takes one
+/// call at a time.
+#[derive(Debug, Clone, Copy)]
+enum ModemCommand {
+    /// Re-query the device list now instead of at the next
+    /// `ENUMERATE_INTERVAL`.
+    Enumerate,
+    Stop,
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct QualityChange {
+    address: u8,
+    old_q: u8,
+    new_q: u8,
+}
+
+/// How `start_record` writes a recording. Every field may be left out: the
+/// default is a new file in the recordings directory with the default fields,
+/// comma separated and without limits.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct RecordOptions {
+    path: Option<String>,
+    fields: Option<Vec<String>>,
+    include_beacons: bool,
+    delimiter: Option<String>,
+    append: bool,
+    max_duration_ms: Option<u64>,
+    max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ValidationReport {
+    is_valid: bool,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    beacons: usize,
+    floors: usize,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct AboutInfo {
+    version: String,
+    marvelmind_version: String,
+    os: String,
+    arch: String,
+    is_connected: bool,
+    port: u32,
+    beacons: usize,
+    hedgehogs: usize,
+    device_types: Vec<String>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ModemInfo {
+    address: Option<u8>,
+    dtype: Option<String>,
+    firmware: Option<String>,
+    is_connected: bool,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct GroupedDevices {
+    beacons: Vec<TRDevice>,
+    hedgehogs: Vec<TRDevice>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct DistanceMatrix {
+    addresses: Vec<u8>,
+    distances: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum Units {
+    #[default]
+    #[serde(rename = "m")]
+    Meters,
+    #[serde(rename = "ft")]
+    Feet,
+}
+
+impl Units {
+    fn parse(unit: &str) -> Option<Units> {
+        match unit {
+            "m" => Some(Units::Meters),
+            "ft" => Some(Units::Feet),
+            _ => None,
+        }
+    }
+
+    fn per_meter(&self) -> f64 {
+        match self {
+            Units::Meters => 1.0,
+            Units::Feet => FEET_PER_METER,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AppState {
+    is_mmrunning: bool,
+    stop: Arc<AtomicBool>,
+    mm_done: Arc<AtomicBool>,
+    modem_commands: Option<mpsc::Sender<ModemCommand>>,
+    port: u32,
+    devices: Vec<TRDevice>,
+    tracks: HashMap<u8, DeviceTrack>,
+    smoothing_window: usize,
+    max_jump_m: f64,
+    quality_threshold: u8,
+    quality_alerts: Vec<u8>,
+    units: Units,
+    origin_offset: (f64, f64),
+    lost_timeout_ms: u64,
+    trajectory_limit: usize,
+    trail_duration_ms: u64,
+    emit_rate_hz: f64,
+    loop_timer: LoopTimer,
+    throughput: Throughput,
+    plan: Option<TRPlan>,
+    zones: Vec<Zone>,
+    colors: HashMap<u8, String>,
+    address_filter: HashSet<u8>,
+    filter_recording: bool,
+    last_update_ms: u64,
+    savefile: Option<Recording>,
+    recording_paused: bool,
+    playback_stop: Arc<AtomicBool>,
+    playback_speed: f64,
+    playback_seek: Option<u64>,
+    simulation_stop: Arc<AtomicBool>,
+    ws_server: Option<WsServer>,
+    mqtt: Option<MqttPublisher>,
+    http_server: Option<HttpServer>,
+    settings_path: Option<PathBuf>,
+    recordings_dir: PathBuf,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState {
+            is_mmrunning: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            mm_done: Arc::new(AtomicBool::new(true)),
+            modem_commands: None,
+            port: DEFAULT_PORT,
+            devices: Vec::<TRDevice>::new(),
+            tracks: HashMap::<u8, DeviceTrack>::new(),
+            smoothing_window: 1,
+            max_jump_m: 0.0,
+            quality_threshold: 0,
+            quality_alerts: vec![DEFAULT_QUALITY_ALERT],
+            units: Units::Meters,
+            origin_offset: (0.0, 0.0),
+            lost_timeout_ms: DEFAULT_LOST_TIMEOUT_MS,
+            trajectory_limit: DEFAULT_TRAJECTORY_LIMIT,
+            trail_duration_ms: 0,
+            emit_rate_hz: DEFAULT_EMIT_RATE_HZ,
+            loop_timer: LoopTimer::default(),
+            throughput: Throughput::default(),
+            plan: None,
+            zones: Vec::<Zone>::new(),
+            colors: HashMap::<u8, String>::new(),
+            address_filter: HashSet::<u8>::new(),
+            filter_recording: false,
+            last_update_ms: 0,
+            savefile: None,
+            recording_paused: false,
+            playback_stop: Arc::new(AtomicBool::new(false)),
+            playback_speed: 1.0,
+            playback_seek: None,
+            simulation_stop: Arc::new(AtomicBool::new(false)),
+            ws_server: None,
+            mqtt: None,
+            http_server: None,
+            settings_path: None,
+            recordings_dir: PathBuf::from("."),
+        }
+    }
+}
+
+/// Locks the app state, recovering it if a thread panicked while holding the
+/// lock so one panic doesn't break every later command.
+fn lock_state(app: &AppHandle) -> MutexGuard<'_, AppState> {
+    let state = app.state::<Mutex<AppState>>().inner();
+
+    state.lock().unwrap_or_else(|err| {
+        state.clear_poison();
+        send_error(app.clone(), "recovered app state after a panic".to_string());
+        err.into_inner()
+    })
+}
+
+/// Maps a device type to the name reported in `TRDevice::dtype` and whether
+/// it is a hedgehog. Hedgehog names are spelled out since the bindings spell
+/// some of them "Hedgedog"; every other type keeps its variant name.
+fn device_kind(dtype: &mm::DeviceType) -> (String, bool) {
+    match dtype {
+        mm::DeviceType::SuperBeaconHedgedog => ("SuperBeaconHedgehog".into(), true),
+        mm::DeviceType::BeaconHwV45Hedgehog => ("BeaconHwV45Hedgehog".into(), true),
+        mm::DeviceType::BeaconHwV49Hedgehog => ("BeaconHwV49Hedgehog".into(), true),
+        mm::DeviceType::IndustrialSuperBeaconHedgedog => {
+            ("IndustrialSuperBeaconHedgehog".into(), true)
+        }
+        other => (format!("{:?}", other), false),
+    }
+}
+
+/// Applies a new location to the displayed device, returning whether its
+/// position or quality changed.
+fn update_location(state: &mut AppState, location: &Location) -> bool {
+    state.last_update_ms = state.last_update_ms.max(location.t);
+
+    // a device still reporting is not lost, even if its fix is filtered out
+    if let Some(tr_device) = state
+        .devices
+        .iter_mut()
+        .find(|d| d.address == location.address)
+    {
+        tr_device.last_update_ms = location.t;
+    }
+
+    // keep the last good position for low quality fixes
+    if location.q < state.quality_threshold {
+        return false;
+    }
+
+    let window = state.smoothing_window;
+    let trajectory_limit = state.trajectory_limit;
+    let trail_duration_ms = state.trail_duration_ms;
+    let max_jump_m = state.max_jump_m;
+    let track = state.tracks.entry(location.address).or_default();
+
+    // the modem repeats a fix until a new one arrives, and a repeated jump
+    // must not count as another outlier in a row
+    if track.last_t.is_some_and(|t| location.t <= t) {
+        return false;
+    }
+    track.last_t = Some(location.t);
+
+    // hold the previous estimate over sudden jumps
+    if track.is_outlier(location.x, location.y, location.z, max_jump_m) {
+        return false;
+    }
+
+    let velocity = track.update_velocity(location.x, location.y, location.z, location.t);
+    if velocity.is_some() {
+        track.push_window(location.x, location.y, location.z, window);
+    }
+    let (x, y, z) = track
+        .smoothed()
+        .unwrap_or((location.x, location.y, location.z));
+    if velocity.is_some() {
+        track.push_trajectory((x, y, z, location.t), trajectory_limit, trail_duration_ms);
+    }
+
+    // devices may have been cleared by clear_session while running
+    if !state.devices.iter().any(|d| d.address == location.address) {
+        state.devices.push(TRDevice {
+            address: location.address,
+            is_hedge: location.is_hedge,
+            ..Default::default()
+        });
+    }
+
+    let Some(tr_device) = state
+        .devices
+        .iter_mut()
+        .find(|d| d.address == location.address)
+    else {
+        return false;
+    };
+
+    if let Some((vx, vy, vz)) = velocity {
+        tr_device.vx = vx;
+        tr_device.vy = vy;
+        tr_device.vz = vz;
+    }
+    tr_device.last_update_ms = location.t;
+
+    if tr_device.x == x && tr_device.y == y && tr_device.z == z && tr_device.q == location.q {
+        return false;
+    }
+
+    tr_device.x = x;
+    tr_device.y = y;
+    tr_device.z = z;
+    tr_device.q = location.q;
+
+    true
+}
+
+/// Appends a raw location to the current recording. A failed write closes the
+/// recording and is returned, while the caller carries on without it.
+fn write_location(
+    state: &mut AppState,
+    location: &Location,
+    source: TimeSource,
+) -> Result<(), Error> {
+    if state.filter_recording && !is_shown(state, location.address) {
+        return Ok(());
+    }
+
+    let Some(savefile) = &mut state.savefile else {
+        return Ok(());
+    };
+
+    match savefile.write(location, source) {
+        Ok(bytes) => {
+            state.throughput.bytes_written += bytes as u64;
+            Ok(())
+        }
+        Err(err) => {
+            state.savefile = None;
+            Err(err)
+        }
+    }
+}
+
+/// What `record_location` reports after writing a location.
+#[derive(Debug)]
+enum RecordEvent {
+    /// A write failed, logged as an error.
+    Error(String),
+    /// A limit set by `start_record` was reached, emitted as
+    /// `record-auto-stopped`.
+    AutoStopped(RecordSummary),
+}
+
+/// Appends a raw location to the current recording, closing it once a write
+/// fails or a limit set by `start_record` is reached, and returns what is to
+/// be reported.
+fn record(state: &mut AppState, location: &Location, source: TimeSource) -> Vec<RecordEvent> {
+    let mut events = Vec::<RecordEvent>::new();
+
+    if let Err(err) = write_location(state, location, source) {
+        events.push(RecordEvent::Error(format!(
+            "failed to write record: {:#}",
+            err
+        )));
+        return events;
+    }
+
+    if state
+        .savefile
+        .as_ref()
+        .is_some_and(Recording::is_limit_reached)
+    {
+        if let Some(mut savefile) = state.savefile.take() {
+            if let Err(err) = savefile.flush() {
+                events.push(RecordEvent::Error(format!(
+                    "failed to write record: {:#}",
+                    err
+                )));
+            }
+            events.push(RecordEvent::AutoStopped(savefile.summary()));
+        }
+        state.recording_paused = false;
+    }
+
+    events
+}
+
+/// Appends a raw location to the current recording, logging a failed write
+/// and stopping once a limit set by `start_record` is reached.
+fn record_location(app: &AppHandle, state: &mut AppState, location: &Location, source: TimeSource) {
+    for event in record(state, location, source) {
+        match event {
+            RecordEvent::Error(msg) => send_error(app.clone(), msg),
+            RecordEvent::AutoStopped(summary) => {
+                app.emit("record-auto-stopped", summary).unwrap();
+            }
+        }
+    }
+}
+
+/// Emits `device-lost` for devices that stopped reporting within the lost
+/// timeout, and `device-recovered` once they report again.
+fn check_lost(app: &AppHandle, state: &mut AppState) {
+    if state.lost_timeout_ms == 0 {
+        return;
+    }
+
+    let now = unix_millis(SystemTime::now());
+
+    for device in &state.devices {
+        // stationary beacons from the map have never reported
+        if device.last_update_ms == 0 || !is_shown(state, device.address) {
+            continue;
+        }
+
+        let is_lost = now.saturating_sub(device.last_update_ms) > state.lost_timeout_ms;
+        let track = state.tracks.entry(device.address).or_default();
+
+        if is_lost && !track.is_lost {
+            app.emit("device-lost", device.address).unwrap();
+        } else if !is_lost && track.is_lost {
+            app.emit("device-recovered", device.address).unwrap();
+        }
+        track.is_lost = is_lost;
+    }
+}
+
+/// Emits `quality-changed` when a device's quality crosses one of the alert
+/// thresholds, in either direction.
+fn check_quality(app: &AppHandle, state: &mut AppState, location: &Location) {
+    let alerts = &state.quality_alerts;
+    let track = state.tracks.entry(location.address).or_default();
+
+    let Some(old_q) = track.quality.replace(location.q) else {
+        return;
+    };
+    let new_q = location.q;
+
+    let is_crossed = alerts
+        .iter()
+        .any(|&alert| (old_q < alert) != (new_q < alert));
+    if is_crossed && is_shown(state, location.address) {
+        app.emit(
+            "quality-changed",
+            QualityChange {
+                address: location.address,
+                old_q,
+                new_q,
+            },
+        )
+        .unwrap();
+    }
+}
+
+/// The thread of an `open_port` call that timed out and may still be inside
+/// the modem library.
+static PENDING_OPEN: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Waits for a timed out `open_port` thread to finish, so no other modem call
+/// runs alongside it, or it closes a port opened after it. Returns false if
+/// stopped while it is still running.
+fn wait_pending_open(stop: &AtomicBool) -> bool {
+    let mut pending = PENDING_OPEN.lock().unwrap_or_else(PoisonError::into_inner);
+
+    while pending.as_ref().is_some_and(|handle| !handle.is_finished()) {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        sleep(PENDING_OPEN_INTERVAL);
+    }
+
+    if let Some(handle) = pending.take() {
+        let _ = handle.join();
+    }
+
+    true
+}
+
+/// Opens the modem port, giving up once `timeout` passes so a hanging USB
+/// stack doesn't block forever.
+fn open_port(
+    app: &AppHandle,
+    port: u32,
+    timeout: Duration,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    if !wait_pending_open(stop) {
+        return Err(Error::msg("stopped while a previous open was pending"));
+    }
+
+    send_log(app.clone(), format!("opening port {}", port));
+
+    let (sender, receiver) = mpsc::channel();
+
+    // the modem call can't be cancelled, so it runs on its own thread and
+    // closes the port again if it only opens after we gave up
+    let handle = thread::spawn(move || {
+        let result = mm::open_port(port).map_err(|err| err.to_string());
+        if sender.send(result.clone()).is_err() && result.is_ok() {
+            let _ = mm::close_port();
+        }
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => {
+            let _ = handle.join();
+            result.map_err(Error::msg)?
+        }
+        Err(_) => {
+            *PENDING_OPEN.lock().unwrap_or_else(PoisonError::into_inner) = Some(handle);
+            return Err(Error::msg(format!("timed out after {:?}", timeout)));
+        }
+    }
+
+    send_log(app.clone(), format!("opened port {}", port));
+
+    Ok(())
+}
+
+fn mmrun(
+    app: AppHandle,
+    port: u32,
+    max_retries: u32,
+    enumerate_retries: u32,
+    open_timeout: Duration,
+    stop: Arc<AtomicBool>,
+    commands: mpsc::Receiver<ModemCommand>,
+) {
+    if let Err(err) = open_port(&app, port, open_timeout, &stop) {
+        send_error(
+            app.clone(),
+            format!("failed to open port {}: {:#}", port, err),
+        );
+        lock_state(&app).is_mmrunning = false;
+        return;
+    }
+
+    // a modem that was just plugged in may still be busy enumerating
+    let mut attempt = 0;
+    let mut device_list = loop {
+        match mm::get_device_list() {
+            Ok(list) => break list,
+            Err(err) if attempt < enumerate_retries && !stop.load(Ordering::Relaxed) => {
+                attempt += 1;
+                send_warn(
+                    app.clone(),
+                    format!(
+                        "failed to get device list, retrying ({}/{}): {}",
+                        attempt, enumerate_retries, err
+                    ),
+                );
+                sleep(RECONNECT_DELAY * attempt);
+            }
+            Err(err) => {
+                send_error(app.clone(), format!("failed to get device list: {}", err));
+                let _ = mm::close_port();
+                lock_state(&app).is_mmrunning = false;
+                return;
+            }
+        }
+    };
+
+    let mut prev_times = HashMap::<u8, u64>::new();
+    let mut throttle = EmitThrottle::default();
+    let mut enumerated_at: Option<Instant> = None;
+    let mut is_enumerate_requested = false;
+
+    while !stop.load(Ordering::Relaxed) {
+        // re-query the device list now and then, so devices powered on later
+        // show up too
+        let is_initial = enumerated_at.is_none();
+        let is_due = match enumerated_at {
+            Some(t) => is_enumerate_requested || t.elapsed() >= ENUMERATE_INTERVAL,
+            None => true,
+        };
+        is_enumerate_requested = false;
+
+        if is_due && !is_initial {
+            match mm::get_device_list() {
+                Ok(list) => device_list = list,
+                Err(err) => send_warn(app.clone(), format!("failed to get device list: {}", err)),
+            }
+        }
+
+        if is_due {
+            let mut state_lock = lock_state(&app);
+
+            for device in device_list.devices() {
+                let (dtype, is_hedge) = device_kind(&device.dtype());
+
+                let tr_device = TRDevice {
+                    address: device.address(),
+                    dtype,
+                    is_hedge,
+                    x: device.x() as f64 / 1000.0,
+                    y: device.y() as f64 / 1000.0,
+                    z: device.z() as f64 / 1000.0,
+                    q: device.q(),
+                    last_update_ms: unix_millis(device.update_time()),
+                    ..Default::default()
+                };
+
+                if let Some(existing) = state_lock
+                    .devices
+                    .iter_mut()
+                    .find(|d| d.address == tr_device.address)
+                {
+                    // keep what the map knows about the device, and leave
+                    // devices alone once they are tracked
+                    if is_initial {
+                        *existing = TRDevice {
+                            name: existing.name.take(),
+                            ..tr_device
+                        };
+                    }
+                } else {
+                    if is_shown(&state_lock, tr_device.address) {
+                        app.emit("device-added", display_device(&state_lock, &tr_device))
+                            .unwrap();
+                    }
+                    state_lock.devices.push(tr_device);
+                }
+            }
+
+            enumerated_at = Some(Instant::now());
+        }
+
+        if let Err(err) = device_list.update_last_locations() {
+            send_warn(app.clone(), format!("failed to update locations: {}", err));
+            lock_state(&app).loop_timer.error();
+
+            let mut attempt = 0;
+            while !stop.load(Ordering::Relaxed) {
+                if attempt >= max_retries {
+                    send_error(
+                        app.clone(),
+                        format!("gave up reconnecting to port {}", port),
+                    );
+                    lock_state(&app).is_mmrunning = false;
+                    return;
+                }
+                attempt += 1;
+                lock_state(&app).loop_timer.retry();
+
+                sleep(RECONNECT_DELAY * attempt);
+                send_log(
+                    app.clone(),
+                    format!(
+                        "reconnecting to port {} ({}/{})",
+                        port, attempt, max_retries
+                    ),
+                );
+
+                if !wait_pending_open(&stop) {
+                    break;
+                }
+                let _ = mm::close_port();
+                if let Err(err) = open_port(&app, port, open_timeout, &stop) {
+                    send_error(
+                        app.clone(),
+                        format!("failed to open port {}: {:#}", port, err),
+                    );
+                    continue;
+                }
+
+                match mm::get_device_list() {
+                    Ok(list) => {
+                        device_list = list;
+                        break;
+                    }
+                    Err(err) => {
+                        send_error(app.clone(), format!("failed to get device list: {}", err));
+                    }
+                }
+            }
+
+            continue;
+        }
+
+        let mut state_lock = lock_state(&app);
+        state_lock.loop_timer.tick();
+
+        for device in device_list.devices() {
+            if device.q() == 0 {
+                continue;
+            }
+
+            let location = Location {
+                address: device.address(),
+                is_hedge: device_kind(&device.dtype()).1,
+                x: device.x() as f64 / 1000.0,
+                y: device.y() as f64 / 1000.0,
+                z: device.z() as f64 / 1000.0,
+                q: device.q(),
+                t: unix_millis(device.update_time()),
+            };
+
+            throttle.mark(update_location(&mut state_lock, &location));
+            check_quality(&app, &mut state_lock, &location);
+
+            // the modem repeats each device's last fix until a new one
+            // arrives, and only new fixes are counted or recorded
+            let prev_time = prev_times.entry(location.address).or_default();
+            let is_new = location.t > *prev_time;
+            *prev_time = (*prev_time).max(location.t);
+            if !is_new {
+                continue;
+            }
+            state_lock.throughput.updates += 1;
+
+            if state_lock.savefile.is_some() && !state_lock.recording_paused && location.is_hedge {
+                record_location(&app, &mut state_lock, &location, TimeSource::Modem);
+            }
+        }
+
+        if throttle.take(state_lock.emit_rate_hz) {
+            send_devices(&app, &state_lock, &state_lock.devices);
+        }
+
+        check_lost(&app, &mut state_lock);
+        zone::check_zones(&app, &mut state_lock);
+
+        drop(state_lock);
+
+        // waiting on commands instead of sleeping lets them in between
+        // polls without delay
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(ModemCommand::Enumerate) => is_enumerate_requested = true,
+            Ok(ModemCommand::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+    }
+
+    // a port still being opened is closed by its own thread once the open
+    // returns
+    if !wait_pending_open(&stop) {
+        return;
+    }
+    if let Err(err) = mm::close_port() {
+        send_error(
+            app.clone(),
+            format!("failed to close port {}: {}", port, err),
+        );
+    }
+}
+
+/// Connects to the modem on `port`. There is no baud rate to choose:
+/// `mm::open_port` only takes the port number and opens the serial line at
+/// the rate the Marvelmind API picks, which is the only one supported.
+#[tauri::command]
+fn mmstart(
+    app: AppHandle,
+    port: Option<u32>,
+    max_retries: Option<u32>,
+    enumerate_retries: Option<u32>,
+    open_timeout_ms: Option<u64>,
+) {
+    let mut state = lock_state(&app);
+
+    if state.is_mmrunning {
+        return;
+    }
+    // the previous loop may still be stuck in a modem call after mmstop, and
+    // the modem API is global, so a second loop would fight it over the port
+    if !state.mm_done.load(Ordering::Relaxed) {
+        send_warn(
+            app.clone(),
+            "previous connection is still closing, try again shortly".to_string(),
+        );
+        return;
+    }
+    state.is_mmrunning = true;
+    state.stop = Arc::new(AtomicBool::new(false));
+    state.mm_done = Arc::new(AtomicBool::new(false));
+    state.loop_timer = LoopTimer::default();
+    state.port = port.unwrap_or(state.port);
+    save_settings(&app, &state);
+
+    let (sender, commands) = mpsc::channel();
+    state.modem_commands = Some(sender);
+
+    // mmrun blocks in modem calls and sleeps, so it gets a thread of its own
+    // rather than a worker of the async runtime
+    thread::spawn({
+        let app = app.clone();
+        let port = state.port;
+        let stop = state.stop.clone();
+        let done = state.mm_done.clone();
+        let max_retries = max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+        let enumerate_retries = enumerate_retries.unwrap_or(DEFAULT_ENUMERATE_RETRIES);
+        let open_timeout =
+            Duration::from_millis(open_timeout_ms.unwrap_or(DEFAULT_OPEN_TIMEOUT_MS));
+        move || {
+            mmrun(
+                app,
+                port,
+                max_retries,
+                enumerate_retries,
+                open_timeout,
+                stop,
+                commands,
+            );
+            done.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+#[tauri::command]
+fn mmstop(app: AppHandle) {
+    let mut state = lock_state(&app);
+
+    if !state.is_mmrunning {
+        return;
+    }
+
+    state.stop.store(true, Ordering::Relaxed);
+    if let Some(sender) = state.modem_commands.take() {
+        let _ = sender.send(ModemCommand::Stop);
+    }
+    state.is_mmrunning = false;
+}
+
+/// Asks the modem loop to re-query the device list right away, for picking
+/// up a device that was just powered on.
+#[tauri::command]
+fn refresh_devices(app: AppHandle) {
+    let state = lock_state(&app);
+
+    if let Some(sender) = &state.modem_commands {
+        let _ = sender.send(ModemCommand::Enumerate);
+    }
+}
+
+fn send_log_level(app: AppHandle, level: LogLevel, msg: String) {
+    let msg = LogMessage {
+        level,
+        message: msg,
+        timestamp: unix_millis(SystemTime::now()),
+    };
+
+    app.state::<Mutex<Logger>>()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .write(&msg);
+    app.emit("log-message", &msg).unwrap();
+}
+
+/// Logs an info message, also serving as the string-only entry point for
+/// the frontend.
+#[tauri::command]
+fn send_log(app: AppHandle, msg: String) {
+    send_log_level(app, LogLevel::Info, msg);
+}
+
+fn send_warn(app: AppHandle, msg: String) {
+    send_log_level(app, LogLevel::Warn, msg);
+}
+
+fn send_error(app: AppHandle, msg: String) {
+    send_log_level(app, LogLevel::Error, msg);
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Maps a position from the Marvelmind frame into floorplan coordinates, by
+/// rotating it about the origin and then removing the floorplan shift.
+fn transform_point(plan: &TRPlan, x: f64, y: f64) -> (f64, f64) {
+    let (sin, cos) = plan.rotation_deg.to_radians().sin_cos();
+
+    (x * cos - y * sin - plan.x, x * sin + y * cos - plan.y)
+}
+
+/// Converts a position into the coordinates displayed by the frontend.
+fn display_point(state: &AppState, x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+    let scale = state.units.per_meter();
+
+    let (x, y) = match &state.plan {
+        Some(plan) => transform_point(plan, x, y),
+        None => (x, y),
+    };
+    let (x, y) = (x + state.origin_offset.0, y + state.origin_offset.1);
+
+    (x * scale, y * scale, z * scale)
+}
+
+/// Returns a color for a device without one, spreading hues by the golden
+/// angle so neighboring addresses stay distinguishable.
+fn default_color(address: u8) -> String {
+    let hue = (address as f64 * 137.508) % 360.0 / 60.0;
+    let (saturation, lightness) = (0.65, 0.5);
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f64| ((v + m) * 255.0).round() as u8;
+
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+/// Converts a device into the coordinates displayed by the frontend.
+fn display_device(state: &AppState, device: &TRDevice) -> TRDevice {
+    let scale = state.units.per_meter();
+    let mut device = device.clone();
+
+    device.color = Some(match state.colors.get(&device.address) {
+        Some(color) => color.clone(),
+        None => default_color(device.address),
+    });
+
+    (device.x, device.y, device.z) = display_point(state, device.x, device.y, device.z);
+
+    if let Some(plan) = &state.plan {
+        let (sin, cos) = plan.rotation_deg.to_radians().sin_cos();
+        (device.vx, device.vy) = (
+            device.vx * cos - device.vy * sin,
+            device.vx * sin + device.vy * cos,
+        );
+    }
+
+    device.vx *= scale;
+    device.vy *= scale;
+    device.vz *= scale;
+
+    device
+}
+
+/// Whether a device passes the address filter, where an empty filter lets
+/// every device through.
+fn is_shown(state: &AppState, address: u8) -> bool {
+    state.address_filter.is_empty() || state.address_filter.contains(&address)
+}
+
+fn display_devices(state: &AppState, devices: &[TRDevice]) -> Vec<TRDevice> {
+    devices
+        .iter()
+        .filter(|device| is_shown(state, device.address))
+        .map(|device| display_device(state, device))
+        .collect()
+}
+
+/// Converts the floorplan into display units, so `scale_pixels_per_m` holds
+/// pixels per displayed unit and the overlay lines up with the devices.
+fn display_plan(state: &AppState, plan: &TRPlan) -> TRPlan {
+    let scale = state.units.per_meter();
+
+    TRPlan {
+        x: plan.x * scale,
+        y: plan.y * scale,
+        scale_pixels_per_m: plan.scale_pixels_per_m / scale,
+        ..plan.clone()
+    }
+}
+
+/// Returns the value of an attribute in an XML start tag.
+fn svg_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().next_back();
+        let after = rest[start + name.len()..].trim_start();
+        rest = &rest[start + name.len()..];
+
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(after) = after.strip_prefix('=') else {
+            continue;
+        };
+
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let value = &after[1..];
+
+        return value.find(quote).map(|end| &value[..end]);
+    }
+
+    None
+}
+
+/// Returns the size of an SVG image in pixels, from the `width` and `height`
+/// of its root element or else from its `viewBox`. Lengths in absolute units
+/// are converted at 96 pixels per inch, while relative ones such as `%` fall
+/// back to the `viewBox`.
+fn svg_size(data: &[u8]) -> Option<(f64, f64)> {
+    let text = std::str::from_utf8(data).ok()?;
+    let start = text.find("<svg")?;
+    let tag = &text[start..start + text[start..].find('>')?];
+
+    let length = |name: &str| {
+        let value = svg_attribute(tag, name)?.trim();
+        let (number, scale) = SVG_UNITS
+            .into_iter()
+            .find_map(|(unit, scale)| Some((value.strip_suffix(unit)?, scale)))
+            .unwrap_or((value, 1.0));
+
+        number
+            .trim_end()
+            .parse::<f64>()
+            .ok()
+            .map(|number| number * scale)
+    };
+
+    if let (Some(width), Some(height)) = (length("width"), length("height")) {
+        return Some((width, height));
+    }
+
+    let view_box: Vec<f64> = svg_attribute(tag, "viewBox")?
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    match view_box[..] {
+        [_, _, width, height] => Some((width, height)),
+        _ => None,
+    }
+}
+
+/// Returns the pixel width and height of a floor image, reading only the
+/// header of raster images.
+fn image_size(floor: &TRFloor) -> Option<(f64, f64)> {
+    if floor.ext == "svg" {
+        return svg_size(&floor.data);
+    }
+
+    let (width, height) = image::ImageReader::new(Cursor::new(&floor.data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+
+    Some((width as f64, height as f64))
+}
+
+/// Returns the extent of the floorplan images and stationary beacons in
+/// display coordinates, so the frontend can fit them into view. Floorplans
+/// are drawn down and to the right of the origin, while display y points up,
+/// so the images span to their width in x and to minus their height in y.
+#[tauri::command]
+fn get_bounds(app: AppHandle) -> Option<Bounds> {
+    let state = lock_state(&app);
+
+    map_bounds(&state)
+}
+
+fn map_bounds(state: &AppState) -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+    let mut extend = |other: Bounds| {
+        bounds = Some(bounds.map_or(other, |bounds| bounds.union(other)));
+    };
+
+    if let Some(plan) = &state.plan {
+        let scale = display_plan(state, plan).scale_pixels_per_m;
+
+        for (width, height) in plan.floors.iter().filter_map(image_size) {
+            extend(Bounds::point(0.0, 0.0));
+            extend(Bounds::point(width / scale, -height / scale));
+        }
+    }
+
+    for device in display_devices(state, &state.devices) {
+        if device.is_beacon() {
+            extend(Bounds::point(device.x, device.y));
+        }
+    }
+
+    bounds
+}
+
+fn send_devices(app: &AppHandle, state: &AppState, devices: &[TRDevice]) {
+    let devices = display_devices(state, devices);
+
+    if let Some(server) = &state.ws_server {
+        if let Ok(json) = serde_json::to_string(&devices) {
+            server.broadcast(&json);
+        }
+    }
+
+    if let Some(publisher) = &state.mqtt {
+        publisher.publish(&devices);
+    }
+
+    app.emit("devices-updated", devices).unwrap();
+}
+
+/// Sets the number of recent positions averaged for each device in
+/// `read_devices` and `devices-updated`, where 1 disables smoothing.
+/// Samples with zero quality never enter the window, and recordings always
+/// keep the raw positions.
+#[tauri::command]
+fn set_smoothing(app: AppHandle, window: usize) {
+    let mut state = lock_state(&app);
+
+    state.smoothing_window = window.max(1);
+    save_settings(&app, &state);
+}
+
+/// Sets how far in meters a fix may land from the smoothed position before
+/// it is dropped as an outlier, where 0 disables the check. Recordings still
+/// keep every raw sample.
+#[tauri::command]
+fn set_max_jump(app: AppHandle, max_jump_m: f64) {
+    let mut state = lock_state(&app);
+
+    state.max_jump_m = max_jump_m.max(0.0);
+    save_settings(&app, &state);
+}
+
+/// Sets the minimum quality a fix needs to move its device in `read_devices`
+/// and `devices-updated`. Recordings still keep every raw sample.
+#[tauri::command]
+fn set_quality_threshold(app: AppHandle, q: u8) {
+    let mut state = lock_state(&app);
+
+    state.quality_threshold = q;
+    save_settings(&app, &state);
+}
+
+/// Sets the units used by `read_devices`, `devices-updated` and returned
+/// floorplans, either "m" or "ft". Recordings always stay in meters. Returns
+/// the loaded floorplan converted to the new units, if any.
+#[tauri::command]
+fn set_units(app: AppHandle, unit: String) -> Option<TRPlan> {
+    let Some(units) = Units::parse(&unit) else {
+        send_warn(app, format!("unknown unit: {}", unit));
+        return None;
+    };
+
+    let mut state = lock_state(&app);
+
+    state.units = units;
+    save_settings(&app, &state);
+
+    state.plan.as_ref().map(|plan| display_plan(&state, plan))
+}
+
+/// Shifts every displayed position by `dx`, `dy` meters on top of the
+/// floorplan shift, for nudging the map alignment live. Recordings keep the
+/// raw positions.
+#[tauri::command]
+fn set_origin_offset(app: AppHandle, dx: f64, dy: f64) {
+    let mut state = lock_state(&app);
+
+    state.origin_offset = (dx, dy);
+    save_settings(&app, &state);
+    send_devices(&app, &state, &state.devices);
+}
+
+#[tauri::command]
+fn reset_origin_offset(app: AppHandle) {
+    set_origin_offset(app, 0.0, 0.0);
+}
+
+/// Sets how long a device may go without updates before `device-lost` is
+/// emitted for it, where 0 disables the check.
+#[tauri::command]
+fn set_lost_timeout(app: AppHandle, timeout_ms: u64) {
+    let mut state = lock_state(&app);
+
+    state.lost_timeout_ms = timeout_ms;
+    save_settings(&app, &state);
+}
+
+/// Limits `read_devices` and device events to `addresses`, and recordings
+/// too with `filter_recording`. An empty list shows every device.
+#[tauri::command]
+fn set_address_filter(app: AppHandle, addresses: Vec<u8>, filter_recording: Option<bool>) {
+    let mut state = lock_state(&app);
+
+    state.address_filter = addresses.into_iter().collect();
+    state.filter_recording = filter_recording.unwrap_or(false);
+}
+
+/// Sets the qualities below and back above which `quality-changed` is
+/// emitted for a device.
+#[tauri::command]
+fn set_quality_alerts(app: AppHandle, thresholds: Vec<u8>) {
+    let mut state = lock_state(&app);
+
+    state.quality_alerts = thresholds;
+    save_settings(&app, &state);
+}
+
+/// Sets the maximum rate of `devices-updated` events and pushed updates,
+/// where 0 emits every change. Recordings still keep every raw sample.
+#[tauri::command]
+fn set_emit_rate(app: AppHandle, hz: f64) {
+    let mut state = lock_state(&app);
+
+    state.emit_rate_hz = hz.max(0.0);
+    save_settings(&app, &state);
+}
+
+/// Sets how many recent positions are kept per device for `get_trajectory`.
+#[tauri::command]
+fn set_trajectory_limit(app: AppHandle, limit: usize) {
+    let mut state = lock_state(&app);
+
+    state.trajectory_limit = limit;
+    for track in state.tracks.values_mut() {
+        while track.trajectory.len() > limit {
+            track.trajectory.pop_front();
+        }
+    }
+    save_settings(&app, &state);
+}
+
+/// Sets how long positions stay in the trajectory before being dropped,
+/// where 0 keeps them until `set_trajectory_limit` pushes them out.
+#[tauri::command]
+fn set_trail_duration(app: AppHandle, duration_ms: u64) {
+    let mut state = lock_state(&app);
+
+    state.trail_duration_ms = duration_ms;
+    save_settings(&app, &state);
+}
+
+/// Returns up to `max_points` of the most recent positions of a device,
+/// oldest first, leaving out points older than the trail duration.
+#[tauri::command]
+fn get_trajectory(app: AppHandle, address: u8, max_points: usize) -> Vec<TrailPoint> {
+    let state = lock_state(&app);
+    let now = unix_millis(SystemTime::now());
+
+    let Some(track) = state.tracks.get(&address) else {
+        return Vec::new();
+    };
+
+    let points: Vec<TrailPoint> = track
+        .trajectory
+        .iter()
+        .map(|&(x, y, z, t)| {
+            let (x, y, z) = display_point(&state, x, y, z);
+            TrailPoint {
+                x,
+                y,
+                z,
+                t,
+                age_ms: now.saturating_sub(t),
+            }
+        })
+        .filter(|p| state.trail_duration_ms == 0 || p.age_ms <= state.trail_duration_ms)
+        .collect();
+
+    points[points.len().saturating_sub(max_points)..].to_vec()
+}
+
+#[tauri::command]
+fn connection_status(app: AppHandle) -> ConnStatus {
+    let state = lock_state(&app);
+
+    conn_status(&state)
+}
+
+fn conn_status(state: &AppState) -> ConnStatus {
+    ConnStatus {
+        is_running: state.is_mmrunning,
+        port: state.port,
+        device_count: state.devices.len(),
+        last_update_age_ms: match state.last_update_ms {
+            0 => None,
+            t => Some(unix_millis(SystemTime::now()).saturating_sub(t)),
+        },
+    }
+}
+
+/// Clears devices, their history and the current recording, so repeated
+/// trials can start from a clean slate.
+#[tauri::command]
+fn clear_session(app: AppHandle) {
+    let mut state = lock_state(&app);
+
+    state.devices.clear();
+    state.tracks.clear();
+    state.last_update_ms = 0;
+    state.savefile = None;
+    state.recording_paused = false;
+
+    app.emit("session-cleared", ()).unwrap();
+}
+
+/// Sets the `#rrggbb` color a device is drawn with, which is kept with the
+/// settings.
+#[tauri::command]
+fn set_device_color(app: AppHandle, address: u8, color: String) {
+    let is_hex = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex {
+        send_warn(app, format!("invalid color: {}", color));
+        return;
+    }
+
+    let mut state = lock_state(&app);
+
+    state.colors.insert(address, color.to_lowercase());
+    save_settings(&app, &state);
+}
+
+#[tauri::command]
+fn read_devices(app: AppHandle) -> Vec<TRDevice> {
+    let state = lock_state(&app);
+
+    display_devices(&state, &state.devices)
+}
+
+/// Returns the app and library versions, the platform and a summary of the
+/// known devices, for pasting into bug reports. The bindings don't report
+/// firmware versions, so devices are only summarized by type.
+#[tauri::command]
+fn about_info(app: AppHandle) -> AboutInfo {
+    let state = lock_state(&app);
+
+    let mut device_types: Vec<String> = state.devices.iter().map(|d| d.dtype.clone()).collect();
+    device_types.sort();
+    device_types.dedup();
+
+    AboutInfo {
+        version: env!("CARGO_PKG_VERSION").into(),
+        marvelmind_version: env!("MARVELMIND_VERSION").into(),
+        os: std::env::consts::OS.into(),
+        arch: std::env::consts::ARCH.into(),
+        is_connected: state.is_mmrunning,
+        port: state.port,
+        beacons: state.devices.iter().filter(|d| d.is_beacon()).count(),
+        hedgehogs: state.devices.iter().filter(|d| d.is_hedge).count(),
+        device_types,
+    }
+}
+
+/// Returns the modem's address and type from the device list, which stays
+/// known after `mmrun` stops, and whether `mmrun` is connected. The bindings
+/// don't report firmware versions, so `firmware` is always `None`.
+#[tauri::command]
+fn modem_info(app: AppHandle) -> ModemInfo {
+    let state = lock_state(&app);
+
+    let modem = state.devices.iter().find(|d| d.is_modem());
+
+    ModemInfo {
+        address: modem.map(|d| d.address),
+        dtype: modem.map(|d| d.dtype.clone()),
+        firmware: None,
+        is_connected: state.is_mmrunning,
+    }
+}
+
+/// Returns the same devices as `read_devices`, split into stationary beacons
+/// and hedgehogs. The modem is in neither.
+#[tauri::command]
+fn read_devices_grouped(app: AppHandle) -> GroupedDevices {
+    let state = lock_state(&app);
+
+    let devices = display_devices(&state, &state.devices);
+
+    GroupedDevices {
+        beacons: devices.iter().filter(|d| d.is_beacon()).cloned().collect(),
+        hedgehogs: devices.into_iter().filter(|d| d.is_hedge).collect(),
+    }
+}
+
+fn distance(a: &TRDevice, b: &TRDevice) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+/// Returns the current distance in meters between two devices, or `None` if
+/// either is unknown.
+#[tauri::command]
+fn distance_between(app: AppHandle, a: u8, b: u8) -> Option<f64> {
+    let state = lock_state(&app);
+
+    let a = state.devices.iter().find(|d| d.address == a)?;
+    let b = state.devices.iter().find(|d| d.address == b)?;
+
+    Some(distance(a, b))
+}
+
+/// Returns the pairwise distances in meters between all stationary beacons,
+/// with rows and columns ordered by `addresses`.
+#[tauri::command]
+fn beacon_distances(app: AppHandle) -> DistanceMatrix {
+    let state = lock_state(&app);
+
+    let mut beacons: Vec<&TRDevice> = state.devices.iter().filter(|d| d.is_beacon()).collect();
+    beacons.sort_by_key(|d| d.address);
+
+    DistanceMatrix {
+        addresses: beacons.iter().map(|d| d.address).collect(),
+        distances: beacons
+            .iter()
+            .map(|a| beacons.iter().map(|b| distance(a, b)).collect())
+            .collect(),
+    }
+}
+
+#[tauri::command]
+fn list_ports(app: AppHandle) -> Vec<PortInfo> {
+    let ports = match serialport::available_ports() {
+        Ok(ports) => ports,
+        Err(err) => {
+            send_error(app, format!("failed to enumerate serial ports: {}", err));
+            return Vec::<PortInfo>::new();
+        }
+    };
+
+    ports
+        .into_iter()
+        .map(|port| match port.port_type {
+            serialport::SerialPortType::UsbPort(usb) => PortInfo {
+                name: port.port_name,
+                vid: Some(usb.vid),
+                pid: Some(usb.pid),
+                manufacturer: usb.manufacturer,
+                product: usb.product,
+                serial_number: usb.serial_number,
+            },
+            _ => PortInfo {
+                name: port.port_name,
+                ..Default::default()
+            },
+        })
+        .collect()
+}
+
+#[tauri::command]
+fn parse_map(app: AppHandle, path: String) -> ParsedMap {
+    let (devices, plan) = match parse_ini(path, true) {
+        Ok(map) => map,
+        Err(err) => {
+            let msg = format!("failed to parse ini map file: {:#}", err);
+            send_error(app.clone(), msg.clone());
+            return ParsedMap {
+                error: Some(msg),
+                ..Default::default()
+            };
+        }
+    };
+
+    let mut state = lock_state(&app);
+
+    for device in &devices {
+        if !state.devices.iter().any(|d| d.address == device.address) {
+            state.devices.push(device.clone());
+        }
+    }
+    state.plan = Some(plan.clone());
+
+    let devices = display_devices(&state, &devices);
+    let plan = display_plan(&state, &plan);
+
+    app.emit(
+        "map-loaded",
+        MapLoaded {
+            plan: plan.clone(),
+            bounds: map_bounds(&state),
+            devices: devices.clone(),
+        },
+    )
+    .unwrap();
+
+    ParsedMap {
+        devices,
+        plan: Some(plan),
+        error: None,
+    }
+}
+
+/// Lists `[beacon N]` sections that no enabled `beaconN` key in `[devices]`
+/// refers to, which are most likely typos.
+fn unused_beacon_sections(path: &str) -> Result<Vec<String>, Error> {
+    let ini = Ini::load_from_file_noescape(path)?;
+
+    let enabled: HashSet<&str> = ini
+        .section(Some("devices"))
+        .into_iter()
+        .flat_map(|devices| devices.iter())
+        .filter(|(key, value)| key.starts_with("beacon") && *value == "1")
+        .map(|(key, _)| &key[6..])
+        .collect();
+
+    let unused = ini
+        .sections()
+        .flatten()
+        .filter_map(|name| name.strip_prefix("beacon "))
+        .filter(|index| !enabled.contains(index))
+        .map(|index| format!("unused section: [beacon {}]", index))
+        .collect();
+
+    Ok(unused)
+}
+
+/// Checks a map the way `parse_map` would, without reading its floor images,
+/// for quick feedback while editing one.
+#[tauri::command]
+fn validate_ini(path: String) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    match parse_ini_all(&path, false) {
+        Ok((devices, plan, errors)) => {
+            report.is_valid = errors.is_empty();
+            report.errors = errors.iter().map(|err| format!("{:#}", err)).collect();
+            report.beacons = devices.len();
+            report.floors = plan.floors.len();
+        }
+        Err(err) => report.errors.push(format!("{:#}", err)),
+    }
+
+    if let Ok(unused) = unused_beacon_sections(&path) {
+        report.warnings = unused;
+    }
+
+    report
 }
 
-#[derive(Debug)]
-struct AppState {
-    is_mmrunning: bool,
-    devices: Vec<TRDevice>,
-    savefile: Option<File>,
+/// Returns a floor image of the loaded map as a data URL, which can be used
+/// directly as an image source.
+#[tauri::command]
+fn floor_data_url(app: AppHandle, floor: usize) -> Option<String> {
+    let state = lock_state(&app);
+
+    state
+        .plan
+        .as_ref()?
+        .floors
+        .get(floor)
+        .map(TRFloor::data_url)
 }
 
-fn mmrun(app: AppHandle) {
-    let state = app.state::<Mutex<AppState>>();
+#[tauri::command]
+fn export_ini(app: AppHandle, path: String) -> Result<(), String> {
+    let state = lock_state(&app);
 
-    unwrap_or_return!(mm::open_port(5), app.clone());
-    let mut device_list = unwrap_or_return!(mm::get_device_list(), app.clone());
+    let result = match &state.plan {
+        Some(plan) => write_ini(path, plan, &state.devices),
+        None => Err(Error::msg("no map loaded")),
+    };
 
-    let mut state_lock = state.lock().unwrap();
+    result.map_err(|err| {
+        let msg = format!("failed to export ini map file: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
 
-    for device in device_list.devices() {
-        let tr_device = TRDevice {
-            address: device.address(),
-            is_hedge: matches!(
-                device.dtype(),
-                mm::DeviceType::SuperBeaconHedgedog
-                    | mm::DeviceType::BeaconHwV45Hedgehog
-                    | mm::DeviceType::BeaconHwV49Hedgehog
-                    | mm::DeviceType::IndustrialSuperBeaconHedgedog
-            ),
-            x: device.x() as f64 / 1000.0,
-            y: device.y() as f64 / 1000.0,
-            q: device.q(),
-        };
+fn calibrate(
+    state: &mut AppState,
+    pixel_dist: f64,
+    real_m: f64,
+    ini_path: Option<String>,
+) -> Result<f64, Error> {
+    if !(pixel_dist.is_finite() && pixel_dist > 0.0 && real_m.is_finite() && real_m > 0.0) {
+        return Err(Error::msg("distances must be positive"));
+    }
+
+    let plan = state.plan.as_mut().context("no map loaded")?;
+    plan.scale_pixels_per_m = pixel_dist / real_m;
 
-        state_lock.devices.push(tr_device);
+    if let Some(path) = ini_path {
+        write_ini(path, plan, &state.devices)?;
     }
 
-    drop(state_lock);
+    Ok(plan.scale_pixels_per_m)
+}
 
-    let mut prev_time = SystemTime::UNIX_EPOCH;
+/// Sets the floorplan scale from two image points `pixel_dist` pixels apart
+/// that are known to be `real_m` meters apart, returning the new pixels per
+/// meter. With `ini_path`, the map is also exported there.
+#[tauri::command]
+fn calibrate_scale(
+    app: AppHandle,
+    pixel_dist: f64,
+    real_m: f64,
+    ini_path: Option<String>,
+) -> Result<f64, String> {
+    calibrate(&mut lock_state(&app), pixel_dist, real_m, ini_path).map_err(|err| {
+        let msg = format!("failed to calibrate scale: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
 
-    loop {
-        unwrap_or_return!(device_list.update_last_locations(), app.clone());
+#[tauri::command]
+fn start_record(app: AppHandle, options: Option<RecordOptions>) -> Result<String, String> {
+    let RecordOptions {
+        path,
+        fields,
+        include_beacons,
+        delimiter,
+        append,
+        max_duration_ms,
+        max_bytes,
+    } = options.unwrap_or_default();
 
-        let mut state_lock = state.lock().unwrap();
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = lock_state(&app).recordings_dir.clone();
+            std::fs::create_dir_all(&dir).map_err(|err| {
+                let msg = format!(
+                    "failed to start record: failed to create {}: {}",
+                    dir.display(),
+                    err
+                );
+                send_error(app.clone(), msg.clone());
+                msg
+            })?;
+            dir.join(chrono::Local::now().format(RECORD_NAME_FORMAT).to_string())
+        }
+    };
 
-        for device in device_list.devices() {
-            if device.q() > 0 {
-                if let Some(tr_device) = state_lock
-                    .devices
-                    .iter_mut()
-                    .find(|d| d.address == device.address())
-                {
-                    tr_device.x = device.x() as f64 / 1000.0;
-                    tr_device.y = device.y() as f64 / 1000.0;
-                    tr_device.q = device.q();
+    let mut fields = match fields {
+        Some(names) => {
+            let mut fields = Vec::<RecordField>::new();
+            for name in names {
+                let Some(field) = RecordField::parse(&name) else {
+                    let msg = format!("failed to start record: unknown field: {}", name);
+                    send_error(app.clone(), msg.clone());
+                    return Err(msg);
                 };
+                fields.push(field);
+            }
+            if fields.is_empty() {
+                let msg = "failed to start record: no fields".to_string();
+                send_error(app.clone(), msg.clone());
+                return Err(msg);
+            }
+            fields
+        }
+        None => RecordField::DEFAULT.to_vec(),
+    };
 
-                if let Some(savefile) = &mut state_lock.savefile {
-                    if !matches!(
-                        device.dtype(),
-                        mm::DeviceType::SuperBeaconHedgedog
-                            | mm::DeviceType::BeaconHwV45Hedgehog
-                            | mm::DeviceType::BeaconHwV49Hedgehog
-                            | mm::DeviceType::IndustrialSuperBeaconHedgedog
-                    ) {
-                        continue;
-                    }
-
-                    if device.update_time() <= prev_time {
-                        continue;
-                    }
+    // beacon rows are only told apart from hedgehog rows by this column
+    if include_beacons && !fields.contains(&RecordField::IsHedge) {
+        fields.push(RecordField::IsHedge);
+    }
 
-                    savefile
-                        .write(
-                            format!(
-                                "{},{},{},{},{},{}\n",
-                                device.address(),
-                                device.x(),
-                                device.y(),
-                                device.z(),
-                                device.q(),
-                                device
-                                    .update_time()
-                                    .duration_since(SystemTime::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_millis(),
-                            )
-                            .as_bytes(),
-                        )
-                        .unwrap();
-
-                    prev_time = device.update_time();
-                }
+    let delimiter = match delimiter.as_deref() {
+        None => ',',
+        Some(delimiter) => match record::DELIMITERS
+            .into_iter()
+            .find(|d| d.to_string() == delimiter)
+        {
+            Some(delimiter) => delimiter,
+            None => {
+                let msg = format!("failed to start record: unknown delimiter: {:?}", delimiter);
+                send_error(app.clone(), msg.clone());
+                return Err(msg);
             }
+        },
+    };
+
+    if append {
+        match record::header_mismatch(&path, &fields, delimiter) {
+            Ok(Some(existing)) => send_warn(
+                app.clone(),
+                format!(
+                    "appending to {} whose header {} doesn't match the recorded fields",
+                    path.display(),
+                    existing
+                ),
+            ),
+            Ok(None) => {}
+            Err(err) => send_warn(
+                app.clone(),
+                format!("failed to check header of {}: {:#}", path.display(), err),
+            ),
         }
+    }
+
+    let mut savefile = Recording::create(path, fields, delimiter, append).map_err(|err| {
+        let msg = format!("failed to start record: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })?;
+    savefile.set_limits(max_duration_ms, max_bytes);
 
-        sleep(Duration::from_millis(1));
+    let mut state = lock_state(&app);
+
+    let path = savefile.path().to_string_lossy().into();
+    state.savefile = Some(savefile);
+    state.recording_paused = false;
+
+    // snapshot stationary beacons once so the recording is self-contained
+    if include_beacons {
+        let t = unix_millis(SystemTime::now());
+        let beacons: Vec<Location> = state
+            .devices
+            .iter()
+            .filter(|d| d.is_beacon())
+            .map(|d| Location {
+                address: d.address,
+                is_hedge: false,
+                x: d.x,
+                y: d.y,
+                z: d.z,
+                q: d.q,
+                t,
+            })
+            .collect();
+
+        for beacon in &beacons {
+            record_location(&app, &mut state, beacon, TimeSource::Host);
+        }
     }
+
+    Ok(path)
 }
 
 #[tauri::command]
-fn mmstart(app: AppHandle) {
-    let state = app.state::<Mutex<AppState>>();
-    let mut state = state.lock().unwrap();
+fn stop_record(app: AppHandle) {
+    let mut state = lock_state(&app);
 
-    if state.is_mmrunning {
-        return;
+    if let Some(savefile) = state.savefile.take() {
+        app.emit("record-summary", savefile.summary()).unwrap();
     }
-    state.is_mmrunning = true;
-
-    spawn({
-        let app = app.clone();
-        async move {
-            mmrun(app);
-        }
-    });
+    state.recording_paused = false;
 }
 
+/// Lists the recordings in the directory `start_record` saves to by default,
+/// newest first. A missing directory yields an empty list.
 #[tauri::command]
-fn send_log(app: AppHandle, msg: String) {
-    app.emit("log-message", &msg).unwrap();
+fn list_recordings(app: AppHandle) -> Vec<RecordingInfo> {
+    let dir = lock_state(&app).recordings_dir.clone();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut recordings: Vec<RecordingInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        })
+        .filter_map(|path| match RecordingInfo::read(&path) {
+            Ok(info) => Some(info),
+            Err(err) => {
+                send_warn(app.clone(), format!("failed to read recording: {:#}", err));
+                None
+            }
+        })
+        .collect();
+
+    recordings.sort_by_key(|info| std::cmp::Reverse(info.modified()));
+
+    recordings
 }
 
+/// Writes a marker row and stops appending samples to the current recording,
+/// keeping the file open until `resume_record`.
 #[tauri::command]
-fn read_devices(app: AppHandle) -> Vec<TRDevice> {
-    let state = app.state::<Mutex<AppState>>();
-    let state = state.lock().unwrap();
+fn pause_record(app: AppHandle) {
+    set_record_paused(&app, true);
+}
 
-    state.devices.clone()
+#[tauri::command]
+fn resume_record(app: AppHandle) {
+    set_record_paused(&app, false);
 }
 
+/// Writes a labelled marker at the current time to the current recording,
+/// which playback emits as an `annotation` event.
 #[tauri::command]
-fn parse_map(app: AppHandle, path: String) -> (Vec<TRDevice>, Option<TRPlan>) {
-    let Ok((devices, plan)) = parse_ini(path) else {
-        send_log(app, "failed to parse ini map file".into());
-        return (Vec::<TRDevice>::new(), None);
+fn add_annotation(app: AppHandle, label: String) -> Result<(), String> {
+    let mut state = lock_state(&app);
+
+    let Some(savefile) = &mut state.savefile else {
+        return Err("not recording".into());
     };
 
-    (devices, Some(plan))
+    let t = unix_millis(SystemTime::now());
+
+    if let Err(err) = savefile.write_annotation(t, &label) {
+        let msg = format!("failed to write record: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        state.savefile = None;
+        return Err(msg);
+    }
+
+    Ok(())
 }
 
-#[tauri::command]
-fn start_record(app: AppHandle) {
-    let state = app.state::<Mutex<AppState>>();
-    let mut state = state.lock().unwrap();
+fn set_record_paused(app: &AppHandle, paused: bool) {
+    let mut state = lock_state(app);
+
+    if state.recording_paused == paused {
+        return;
+    }
+
+    let Some(savefile) = &mut state.savefile else {
+        return;
+    };
+
+    let t = unix_millis(SystemTime::now());
+    let marker = match paused {
+        true => format!("paused at {}", t),
+        false => format!("resumed at {}", t),
+    };
 
-    state.savefile = Some(File::create(LOG_PATH).unwrap());
-    if let Some(savefile) = &mut state.savefile {
-        savefile.write("address,x,y,z,q,t\n".as_bytes()).unwrap();
+    if let Err(err) = savefile.write_marker(&marker) {
+        send_error(app.clone(), format!("failed to write record: {:#}", err));
+        state.savefile = None;
+        return;
     }
+
+    state.recording_paused = paused;
 }
 
-#[tauri::command]
-fn stop_record(app: AppHandle) {
-    let state = app.state::<Mutex<AppState>>();
-    let mut state = state.lock().unwrap();
+/// Checks that a floor image has a supported extension, and that raster
+/// images start with the matching magic bytes.
+fn validate_image(floor: &TRFloor) -> Result<(), Error> {
+    // keep in sync with IMAGE_EXTENSIONS
+    let magic: &[u8] = match floor.ext.as_str() {
+        "png" => b"\x89PNG\r\n\x1a\n",
+        "jpg" | "jpeg" => b"\xff\xd8\xff",
+        "bmp" => b"BM",
+        // vector plans are passed through as text for the frontend to embed
+        "svg" => {
+            return match std::str::from_utf8(&floor.data) {
+                Ok(text) if text.contains("<svg") => Ok(()),
+                _ => Err(Error::msg("content is not an svg document")),
+            }
+        }
+        ext => return Err(Error::msg(format!("unsupported extension: {}", ext))),
+    };
 
-    state.savefile = None;
+    if !floor.data.starts_with(magic) {
+        return Err(Error::msg(format!(
+            "content does not match extension: {}",
+            floor.ext
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parses an INI value, naming the section and key when it is malformed.
+fn parse_value<T>(section: &str, key: &str, value: &str) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse::<T>()
+        .with_context(|| format!("invalid value: [{}] {} = {}", section, key, value))
+}
+
+/// Keeps the value of `result`, or stashes its error and returns `None`, so
+/// one malformed key doesn't hide the others.
+fn collect<T>(errors: &mut Vec<Error>, result: Result<T, Error>) -> Option<T> {
+    result.map_err(|err| errors.push(err)).ok()
+}
+
+/// Parses a key that must be present in `section`.
+fn required_value<T>(name: &str, section: &ini::Properties, key: &str) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    parse_value(
+        name,
+        key,
+        section.get(key).context(format!("no value: {}", key))?,
+    )
+}
+
+/// Reads or checks the floor image `value`, resolving a relative path
+/// against the map's own directory.
+fn parse_floor(ini_dir: &Path, value: &str, read_images: bool) -> Result<TRFloor, Error> {
+    let image_path = ini_dir.join(value);
+
+    let data = match read_images {
+        true => std::fs::read(&image_path)
+            .context(format!("failed to read image: {}", image_path.display()))?,
+        false if image_path.is_file() => Vec::new(),
+        false => {
+            return Err(Error::msg(format!(
+                "no image file: {}",
+                image_path.display()
+            )))
+        }
+    };
+
+    let floor = TRFloor {
+        file: value.into(),
+        data,
+        ext: PathBuf::from(value)
+            .extension()
+            .context("failed to read extension")?
+            .to_str()
+            .context("failed to convert extension")?
+            .to_lowercase(),
+        path: std::fs::canonicalize(&image_path).unwrap_or(image_path),
+    };
+
+    if read_images {
+        validate_image(&floor).context(format!("invalid image: {}", value))?;
+    } else if !IMAGE_EXTENSIONS.contains(&floor.ext.as_str()) {
+        return Err(Error::msg(format!("unsupported extension: {}", floor.ext))
+            .context(format!("invalid image: {}", value)));
+    }
+
+    Ok(floor)
+}
+
+/// Parses the `[beacon N]` section of an enabled `beaconN` key, returning
+/// `None` for hedgehogs and for sections with errors.
+fn parse_beacon(ini: &Ini, index: &str, errors: &mut Vec<Error>) -> Option<TRDevice> {
+    let section = format!("beacon {}", index);
+
+    let Some(beacon) = ini.section(Some(section.as_str())) else {
+        errors.push(Error::msg(format!("no section: [{}]", section)));
+        return None;
+    };
+
+    if ini.section_all(Some(section.as_str())).count() > 1 {
+        errors.push(Error::msg(format!("duplicate section: [{}]", section)));
+        return None;
+    }
+
+    let hedgehog_mode = collect(
+        errors,
+        required_value::<u32>(&section, beacon, "Hedgehog_mode"),
+    )?;
+    if hedgehog_mode != 0 {
+        return None;
+    }
+
+    let address = collect(
+        errors,
+        index
+            .parse::<u8>()
+            .with_context(|| format!("invalid beacon address: beacon{}", index)),
+    );
+    let x = collect(errors, required_value(&section, beacon, "Position_X"));
+    let y = collect(errors, required_value(&section, beacon, "Position_Y"));
+    let z = match beacon.get("Position_Z") {
+        Some(z) => collect(errors, parse_value(&section, "Position_Z", z)),
+        None => Some(0.0),
+    };
+
+    Some(TRDevice {
+        x: x?,
+        y: y?,
+        z: z?,
+        address: address?,
+        name: beacon
+            .get("Name")
+            .or_else(|| beacon.get("Description"))
+            .map(|name| name.to_string()),
+        dtype: "Beacon".into(),
+        is_hedge: false,
+        q: 0,
+        last_update_ms: 0,
+        ..Default::default()
+    })
 }
 
-fn parse_ini(path: String) -> Result<(Vec<TRDevice>, TRPlan), Error> {
+/// Parses a Marvelmind Dashboard map, carrying on past malformed keys and
+/// sections and returning their errors along with what could be read. Only
+/// a file that can't be loaded at all, or has no `[floorplan]`, fails
+/// outright.
+fn parse_ini_all(
+    path: &str,
+    read_images: bool,
+) -> Result<(Vec<TRDevice>, TRPlan, Vec<Error>), Error> {
     let mut plan = TRPlan::default();
+    let mut errors = Vec::<Error>::new();
     let ini = Ini::load_from_file_noescape(path)?;
+    let ini_dir = Path::new(path).parent().unwrap_or(Path::new(""));
 
     let floorplan = ini
         .section(Some("floorplan"))
         .context("no section: [floorplan]")?;
 
-    plan.x = floorplan
-        .get("shift_x_m")
-        .context("no value: shift_x_m")?
-        .parse::<f64>()?;
-    plan.y = floorplan
-        .get("shift_y_m")
-        .context("no value: shift_y_m")?
-        .parse::<f64>()?;
-    plan.scale_pixels_per_m = floorplan
-        .get("scale_pixels_per_m")
-        .context("no value: scale_pixels_per_m")?
-        .parse::<f64>()?;
+    // a malformed value leaves its default, so the rest can still be checked
+    plan.x = collect(
+        &mut errors,
+        required_value("floorplan", floorplan, "shift_x_m"),
+    )
+    .unwrap_or_default();
+    plan.y = collect(
+        &mut errors,
+        required_value("floorplan", floorplan, "shift_y_m"),
+    )
+    .unwrap_or_default();
+    plan.scale_pixels_per_m = collect(
+        &mut errors,
+        required_value("floorplan", floorplan, "scale_pixels_per_m"),
+    )
+    .unwrap_or_default();
+    plan.rotation_deg = match floorplan.get("rotation_deg") {
+        Some(rotation_deg) => collect(
+            &mut errors,
+            parse_value("floorplan", "rotation_deg", rotation_deg),
+        )
+        .unwrap_or_default(),
+        None => 0.0,
+    };
 
+    let mut has_floors = false;
     for (key, value) in floorplan {
         if key.starts_with("Floor") {
-            plan.data = std::fs::read(value)?;
-            plan.ext = PathBuf::from(value)
-                .extension()
-                .context("failed to read extension")?
-                .to_str()
-                .context("failed to convert extension")?
-                .into();
-
-            break;
+            has_floors = true;
+            if let Some(floor) = collect(&mut errors, parse_floor(ini_dir, value, read_images)) {
+                plan.floors.push(floor);
+            }
         }
     }
-    if plan.data.is_empty() {
-        return Err(Error::msg("no value: FloorX_FILE"));
+    if !has_floors {
+        errors.push(Error::msg("no value: FloorX_FILE"));
     }
 
     let mut devices = Vec::<TRDevice>::new();
 
-    let tr_devices = ini
-        .section(Some("devices"))
-        .context("no section: [devices]")?;
+    let Some(tr_devices) = ini.section(Some("devices")) else {
+        errors.push(Error::msg("no section: [devices]"));
+        return Ok((devices, plan, errors));
+    };
 
     for (key, value) in tr_devices {
         if !key.starts_with("beacon") {
             continue;
         }
 
-        if value.parse::<u32>()? != 1 {
+        if collect(&mut errors, parse_value::<u32>("devices", key, value)) != Some(1) {
             continue;
         }
 
-        let index = &key[6..];
-
-        let beacon = ini
-            .section(Some(format!("beacon {}", index)))
-            .context(format!("no section: [beacon {}]", index))?;
+        let Some(device) = parse_beacon(&ini, &key[6..], &mut errors) else {
+            continue;
+        };
 
-        if beacon
-            .get("Hedgehog_mode")
-            .context("no value: Hedgehog_mode")?
-            != "0"
-        {
+        // keys like beacon5 and beacon05 would otherwise both claim address 5
+        if devices.iter().any(|d| d.address == device.address) {
+            errors.push(Error::msg(format!(
+                "duplicate beacon address: {}",
+                device.address
+            )));
             continue;
         }
 
-        let device = TRDevice {
-            x: beacon
-                .get("Position_X")
-                .context("no value: Position_X")?
-                .parse::<f64>()?,
-            y: beacon
-                .get("Position_Y")
-                .context("no value: Position_Y")?
-                .parse::<f64>()?,
-            address: index.parse::<u8>()?,
-            is_hedge: false,
-            q: 0,
-        };
-
         devices.push(device);
     }
 
-    Ok((devices, plan))
+    Ok((devices, plan, errors))
+}
+
+/// Parses a Marvelmind Dashboard map, failing on its first error. Without
+/// `read_images`, floor images are only checked to exist and the plan's
+/// floors are left without data.
+fn parse_ini(path: String, read_images: bool) -> Result<(Vec<TRDevice>, TRPlan), Error> {
+    let (devices, plan, errors) = parse_ini_all(&path, read_images)?;
+
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok((devices, plan)),
+    }
+}
+
+/// Returns `path` relative to the directory `base`, both absolute, or `None`
+/// when they share no root, as with paths on different Windows drives.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return None;
+    }
+
+    let ups = base.components().count() - common;
+    let rest = path.components().skip(common);
+
+    Some(
+        std::iter::repeat_n(Component::ParentDir.as_os_str(), ups)
+            .chain(rest.map(|c| c.as_os_str()))
+            .collect(),
+    )
+}
+
+/// Floor image path as it should appear in an INI written to `out_dir`:
+/// relative to it where possible, absolute otherwise.
+fn floor_file(floor: &TRFloor, out_dir: &Path) -> String {
+    if floor.path.as_os_str().is_empty() {
+        return floor.file.clone();
+    }
+    relative_path(&floor.path, out_dir)
+        .unwrap_or_else(|| floor.path.clone())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn write_ini(path: String, plan: &TRPlan, devices: &[TRDevice]) -> Result<(), Error> {
+    let mut ini = Ini::new();
+
+    ini.with_section(Some("floorplan"))
+        .set("shift_x_m", plan.x.to_string())
+        .set("shift_y_m", plan.y.to_string())
+        .set("scale_pixels_per_m", plan.scale_pixels_per_m.to_string())
+        .set("rotation_deg", plan.rotation_deg.to_string());
+
+    let out_dir = Path::new(&path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let out_dir = std::fs::canonicalize(out_dir).context(format!(
+        "failed to resolve directory: {}",
+        out_dir.display()
+    ))?;
+
+    for (index, floor) in plan.floors.iter().enumerate() {
+        ini.with_section(Some("floorplan")).set(
+            format!("Floor{}_FILE", index + 1),
+            floor_file(floor, &out_dir),
+        );
+    }
+
+    // the modem and hedgehogs have no place in a map
+    let beacons: Vec<&TRDevice> = devices.iter().filter(|d| d.is_beacon()).collect();
+
+    for beacon in &beacons {
+        ini.with_section(Some("devices"))
+            .set(format!("beacon{}", beacon.address), "1");
+    }
+
+    for beacon in &beacons {
+        ini.with_section(Some(format!("beacon {}", beacon.address)))
+            .set("Hedgehog_mode", "0")
+            .set("Position_X", beacon.x.to_string())
+            .set("Position_Y", beacon.y.to_string())
+            .set("Position_Z", beacon.z.to_string());
+
+        if let Some(name) = &beacon.name {
+            ini.with_section(Some(format!("beacon {}", beacon.address)))
+                .set("Name", name.clone());
+        }
+    }
+
+    ini.write_to_file_policy(path, ini::EscapePolicy::Nothing)?;
+
+    Ok(())
+}
+
+/// Stops every background task and flushes the current recording, waiting
+/// up to `SHUTDOWN_TIMEOUT` for `mmrun` to close the port.
+fn shutdown(app: &AppHandle) {
+    mmstop(app.clone());
+    playback::stop_playback(app.clone());
+    simulate::stop_simulation(app.clone());
+    ws::stop_ws_server(app.clone());
+    mqtt::mqtt_disconnect(app.clone());
+    http::stop_http_server(app.clone());
+
+    let done = {
+        let mut state = lock_state(app);
+
+        if let Some(mut savefile) = state.savefile.take() {
+            if let Err(err) = savefile.flush() {
+                send_error(app.clone(), format!("failed to flush record: {:#}", err));
+            }
+        }
+
+        state.mm_done.clone()
+    };
+
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    while !done.load(Ordering::Relaxed) && Instant::now() < deadline {
+        sleep(Duration::from_millis(10));
+    }
+}
+
+/// Whether a second window was asked for, for running against two modems at
+/// once. Set `MMVISUAL_MULTI_INSTANCE=1` or pass `--multi-instance`.
+fn multi_instance() -> bool {
+    let env = std::env::var(MULTI_INSTANCE_ENV)
+        .map(|value| !value.is_empty() && value != "0")
+        .unwrap_or(false);
+
+    env || std::env::args().any(|arg| arg == MULTI_INSTANCE_FLAG)
 }
 
 pub fn run() {
     let mut builder = tauri::Builder::default();
 
-    builder = builder.plugin(tauri_plugin_single_instance::init(|_, _, _| {}));
+    if !multi_instance() {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|_, _, _| {}));
+    }
     #[cfg(not(debug_assertions))]
     {
         builder = builder.plugin(tauri_plugin_prevent_default::init());
@@ -310,11 +2576,44 @@ pub fn run() {
             window.open_devtools();
         }
 
-        app.manage(Mutex::new(AppState {
-            is_mmrunning: false,
-            devices: Vec::<TRDevice>::new(),
-            savefile: None,
-        }));
+        let logger = app
+            .path()
+            .app_log_dir()
+            .map_err(Error::from)
+            .and_then(|dir| Logger::open(dir.join(logger::LOG_NAME)))
+            .unwrap_or_default();
+        app.manage(Mutex::new(logger));
+
+        let mut state = AppState {
+            settings_path: app
+                .path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join(settings::SETTINGS_NAME)),
+            ..Default::default()
+        };
+        if let Ok(dir) = app.path().app_data_dir() {
+            state.recordings_dir = dir.join(RECORDINGS_DIR);
+        }
+
+        if let Some(path) = &state.settings_path {
+            if path.exists() {
+                match Settings::load(path) {
+                    Ok(settings) => settings.apply(&mut state),
+                    Err(err) => send_warn(
+                        app.handle().clone(),
+                        format!("failed to load settings: {:#}", err),
+                    ),
+                }
+            }
+        }
+
+        app.manage(Mutex::new(state));
+
+        thread::spawn({
+            let app = app.handle().clone();
+            move || stats::report_throughput(app)
+        });
 
         // prevent pinch zoom by touchpad
         #[cfg(target_os = "linux")]
@@ -339,14 +2638,391 @@ pub fn run() {
 
     builder = builder.invoke_handler(tauri::generate_handler![
         mmstart,
+        mmstop,
+        refresh_devices,
         send_log,
         read_devices,
+        read_devices_grouped,
+        about_info,
+        modem_info,
+        set_device_color,
+        distance_between,
+        beacon_distances,
         start_record,
         stop_record,
-        parse_map
+        pause_record,
+        resume_record,
+        add_annotation,
+        list_recordings,
+        parse_map,
+        validate_ini,
+        get_bounds,
+        floor_data_url,
+        list_ports,
+        connection_status,
+        set_smoothing,
+        set_max_jump,
+        set_quality_threshold,
+        set_units,
+        set_origin_offset,
+        reset_origin_offset,
+        set_lost_timeout,
+        set_trajectory_limit,
+        set_trail_duration,
+        set_quality_alerts,
+        set_address_filter,
+        set_emit_rate,
+        get_trajectory,
+        clear_session,
+        export_ini,
+        calibrate_scale,
+        export::export_gpx,
+        export::export_json,
+        export::decimate_log,
+        export::split_recording,
+        export::merge_recordings,
+        playback::start_playback,
+        playback::stop_playback,
+        playback::load_recording,
+        playback::set_playback_speed,
+        playback::playback_seek,
+        simulate::start_simulation,
+        simulate::stop_simulation,
+        snapshot::export_snapshot_png,
+        snapshot::export_snapshot_csv,
+        stats::position_stats,
+        stats::loop_stats,
+        stats::occupancy_grid,
+        stats::track_metrics,
+        ws::start_ws_server,
+        ws::stop_ws_server,
+        mqtt::mqtt_connect,
+        mqtt::mqtt_disconnect,
+        http::start_http_server,
+        http::stop_http_server,
+        logger::set_log_file,
+        settings::get_settings,
+        settings::reset_settings,
+        zone::set_zones
     ]);
 
     builder
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                shutdown(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an empty directory of its own for a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mmvisual-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn location(t: u64) -> Location {
+        Location {
+            address: 7,
+            is_hedge: true,
+            x: 1.0,
+            y: 2.0,
+            z: 0.0,
+            q: 100,
+            t,
+        }
+    }
+
+    #[test]
+    fn write_error_closes_recording_and_keeps_going() {
+        let dir = temp_dir("write-error");
+        let mut state = AppState {
+            savefile: Some(Recording::read_only(dir.join("record.csv")).unwrap()),
+            ..Default::default()
+        };
+
+        let events = record(&mut state, &location(1), TimeSource::Modem);
+        assert!(matches!(
+            &events[..],
+            [RecordEvent::Error(msg)] if msg.starts_with("failed to write record")
+        ));
+        assert!(state.savefile.is_none());
+
+        // the loop goes on tracking later fixes, which are dropped from the
+        // closed recording quietly instead of failing again
+        assert!(update_location(&mut state, &location(2)));
+        assert_eq!(state.last_update_ms, 2);
+        assert!(record(&mut state, &location(2), TimeSource::Modem).is_empty());
+    }
+
+    #[test]
+    fn svg_size_reads_width_and_height() {
+        let svg = br#"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="640" height='480' viewBox="0 0 10 10">"#;
+
+        assert_eq!(svg_size(svg), Some((640.0, 480.0)));
+    }
+
+    #[test]
+    fn svg_size_falls_back_to_view_box() {
+        assert_eq!(
+            svg_size(br#"<svg viewBox="-5, 0 320 200"></svg>"#),
+            Some((320.0, 200.0))
+        );
+        assert_eq!(
+            svg_size(br#"<svg width="100%" height="100%" viewBox="0 0 50 25">"#),
+            Some((50.0, 25.0))
+        );
+        assert_eq!(svg_size(br#"<svg viewBox="0 0 50">"#), None);
+    }
+
+    #[test]
+    fn svg_size_converts_length_units() {
+        assert_eq!(
+            svg_size(br#"<svg width="300px" height=" 150 px ">"#),
+            Some((300.0, 150.0))
+        );
+        assert_eq!(
+            svg_size(br#"<svg width="2in" height="72pt">"#),
+            Some((192.0, 96.0))
+        );
+
+        let (width, height) = svg_size(br#"<svg width="25.4mm" height="2.54cm">"#).unwrap();
+        assert!((width - 96.0).abs() < 1e-9 && (height - 96.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn floor_file_is_relative_to_output_dir() {
+        let dir = temp_dir("floor-file");
+        let floor = TRFloor {
+            file: "plan.png".into(),
+            path: dir.join("images").join("plan.png"),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            floor_file(&floor, &dir),
+            Path::new("images").join("plan.png").to_string_lossy()
+        );
+        assert_eq!(
+            floor_file(&floor, &dir.join("other")),
+            Path::new("..")
+                .join("images")
+                .join("plan.png")
+                .to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn filtered_fixes_still_refresh_last_update() {
+        let mut state = AppState {
+            devices: vec![TRDevice {
+                address: 7,
+                is_hedge: true,
+                ..Default::default()
+            }],
+            quality_threshold: 50,
+            ..Default::default()
+        };
+
+        let low_quality = Location {
+            q: 10,
+            ..location(5)
+        };
+        assert!(!update_location(&mut state, &low_quality));
+        assert_eq!(state.devices[0].last_update_ms, 5);
+        assert_eq!(state.devices[0].q, 0);
+    }
+
+    /// Writes a map into `dir/maps` with the given floor and devices lines,
+    /// returning its path.
+    fn write_map(dir: &Path, floor: &str, devices: &str) -> String {
+        let maps = dir.join("maps");
+        std::fs::create_dir_all(&maps).unwrap();
+
+        let path = maps.join("plan.ini");
+        std::fs::write(
+            &path,
+            format!(
+                "[floorplan]\nshift_x_m = 0\nshift_y_m = 0\nscale_pixels_per_m = 100\n\
+                 Floor1_FILE = {}\n[devices]\n{}",
+                floor, devices
+            ),
+        )
+        .unwrap();
+
+        path.to_string_lossy().into()
+    }
+
+    #[test]
+    fn floor_images_resolve_against_the_map() {
+        let dir = temp_dir("relative-floor");
+        std::fs::create_dir_all(dir.join("images")).unwrap();
+        std::fs::write(dir.join("images").join("plan.svg"), "<svg></svg>").unwrap();
+        let path = write_map(&dir, "../images/plan.svg", "");
+
+        for read_images in [false, true] {
+            let (_, plan) = parse_ini(path.clone(), read_images).unwrap();
+            assert_eq!(plan.floors[0].file, "../images/plan.svg");
+            assert_eq!(plan.floors[0].ext, "svg");
+            assert_eq!(
+                plan.floors[0].path,
+                std::fs::canonicalize(dir.join("images").join("plan.svg")).unwrap()
+            );
+            // images are only loaded when asked for
+            assert_eq!(plan.floors[0].data.is_empty(), !read_images);
+        }
+    }
+
+    #[test]
+    fn missing_floor_image_is_an_error() {
+        let dir = temp_dir("missing-floor");
+        let path = write_map(&dir, "../images/plan.svg", "");
+
+        assert!(parse_ini(path.clone(), false).is_err());
+        assert!(parse_ini(path, true).is_err());
+    }
+
+    fn beacon_section(address: &str) -> String {
+        format!(
+            "[beacon {}]\nHedgehog_mode = 0\nPosition_X = 1\nPosition_Y = 2\n",
+            address
+        )
+    }
+
+    #[test]
+    fn duplicate_beacon_sections_are_rejected() {
+        let dir = temp_dir("duplicate-section");
+        std::fs::write(dir.join("plan.svg"), "<svg></svg>").unwrap();
+        let path = write_map(
+            &dir,
+            "../plan.svg",
+            &format!(
+                "beacon5 = 1\n{}{}",
+                beacon_section("5"),
+                beacon_section("5")
+            ),
+        );
+
+        let err = parse_ini(path, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("duplicate section: [beacon 5]"));
+    }
+
+    #[test]
+    fn padded_beacon_keys_are_rejected_as_duplicates() {
+        let dir = temp_dir("duplicate-address");
+        std::fs::write(dir.join("plan.svg"), "<svg></svg>").unwrap();
+        let path = write_map(
+            &dir,
+            "../plan.svg",
+            &format!(
+                "beacon5 = 1\nbeacon05 = 1\n{}{}",
+                beacon_section("5"),
+                beacon_section("05")
+            ),
+        );
+
+        let err = parse_ini(path, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("duplicate beacon address: 5"));
+    }
+
+    #[test]
+    fn validation_reports_every_error() {
+        let dir = temp_dir("validate-all");
+        let path = write_map(
+            &dir,
+            "../missing.svg",
+            &format!(
+                "beacon1 = 1\nbeacon2 = 1\nbeacon3 = 1\n{}[beacon 2]\nHedgehog_mode = 0\n\
+                 Position_X = east\nPosition_Y = north\n",
+                beacon_section("1")
+            ),
+        );
+
+        let report = validate_ini(path);
+        assert!(!report.is_valid);
+        assert_eq!(report.beacons, 1);
+        assert_eq!(report.floors, 0);
+
+        let errors = report.errors.join("\n");
+        for expected in [
+            "no image file",
+            "invalid value: [beacon 2] Position_X = east",
+            "invalid value: [beacon 2] Position_Y = north",
+            "no section: [beacon 3]",
+        ] {
+            assert!(errors.contains(expected), "{} not in {}", expected, errors);
+        }
+        assert_eq!(report.errors.len(), 4);
+    }
+
+    #[test]
+    fn repeated_polls_are_not_outliers() {
+        let mut state = AppState {
+            max_jump_m: 1.0,
+            ..Default::default()
+        };
+        assert!(update_location(&mut state, &location(1)));
+
+        let jump = |t| Location {
+            x: 10.0,
+            ..location(t)
+        };
+        for _ in 0..=MAX_OUTLIER_RUN {
+            assert!(!update_location(&mut state, &jump(2)));
+        }
+        assert_eq!(state.tracks[&7].outliers, 1);
+
+        // only distinct fixes make up a run long enough to accept the jump
+        for t in 3..=MAX_OUTLIER_RUN as u64 + 2 {
+            update_location(&mut state, &jump(t));
+        }
+        assert_eq!(state.devices[0].x, 10.0);
+    }
+
+    #[test]
+    fn exported_map_parses_back_the_same() {
+        let dir = temp_dir("export-round-trip");
+        std::fs::create_dir_all(dir.join("images")).unwrap();
+        std::fs::write(dir.join("images").join("plan.svg"), "<svg></svg>").unwrap();
+        let path = write_map(
+            &dir,
+            "../images/plan.svg",
+            &format!(
+                "beacon1 = 1\nbeacon2 = 1\n{}{}Name = corner\n",
+                beacon_section("1"),
+                beacon_section("2")
+            ),
+        );
+        let (devices, plan) = parse_ini(path, false).unwrap();
+
+        // live devices that are not beacons must not come back as beacons
+        let mut live = devices.clone();
+        for (address, dtype, is_hedge) in
+            [(9, "ModemHwV51", false), (7, "BeaconHwV49Hedgehog", true)]
+        {
+            live.push(TRDevice {
+                address,
+                dtype: dtype.into(),
+                is_hedge,
+                ..Default::default()
+            });
+        }
+
+        let exported = dir
+            .join("maps")
+            .join("exported.ini")
+            .to_string_lossy()
+            .into_owned();
+        write_ini(exported.clone(), &plan, &live).unwrap();
+
+        assert_eq!(parse_ini(exported, false).unwrap(), (devices, plan));
+    }
 }
@@ -0,0 +1,116 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::{Context, Error, Result};
+use tauri::{AppHandle, Manager};
+
+use crate::{send_error, LogMessage};
+
+pub(crate) const LOG_NAME: &str = "mmvisual.log";
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Persists log messages to a file, rotating it to `<path>.1` once it grows
+/// past `MAX_LOG_BYTES`.
+///
+/// This is kept apart from `AppState` since messages are often logged while
+/// the state is locked.
+#[derive(Debug, Default)]
+pub(crate) struct Logger {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    size: u64,
+}
+
+impl Logger {
+    pub(crate) fn open(path: PathBuf) -> Result<Logger, Error> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)
+                    .context(format!("failed to create directory: {}", parent.display()))?;
+            }
+        }
+
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Logger {
+            path: Some(path),
+            file: Some(file),
+            size,
+        })
+    }
+
+    pub(crate) fn write(&mut self, msg: &LogMessage) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+
+        let line = format!(
+            "{} {:<5} {}\n",
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+            format!("{:?}", msg.level).to_uppercase(),
+            msg.message
+        );
+
+        // failures to log can't be reported anywhere, so they are ignored
+        if file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+
+        if self.size > MAX_LOG_BYTES {
+            let _ = self.rotate();
+        }
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+
+        self.file = None;
+        std::fs::rename(path, rotated)?;
+        self.file = Some(open_append(path)?);
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("failed to open file: {}", path.display()))
+}
+
+/// Sets the file log messages are persisted to, or disables it with `None`.
+#[tauri::command]
+pub(crate) fn set_log_file(app: AppHandle, path: Option<String>) {
+    let logger = match path {
+        Some(path) => match Logger::open(PathBuf::from(path)) {
+            Ok(logger) => logger,
+            Err(err) => {
+                send_error(app, format!("failed to open log file: {:#}", err));
+                return;
+            }
+        },
+        None => Logger::default(),
+    };
+
+    let state = app.state::<Mutex<Logger>>();
+    *state.lock().unwrap_or_else(PoisonError::into_inner) = logger;
+}
@@ -0,0 +1,126 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use rumqttc::{Client, Connection, MqttOptions, QoS};
+use tauri::AppHandle;
+
+use crate::{lock_state, send_error, send_warn, TRDevice};
+
+const DEFAULT_MQTT_PORT: u16 = 1883;
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Publishes every device update to `{prefix}/{address}` on an MQTT broker.
+pub(crate) struct MqttPublisher {
+    client: Client,
+    prefix: String,
+    stop: Arc<AtomicBool>,
+}
+
+// the client has no Debug of its own
+impl fmt::Debug for MqttPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttPublisher")
+            .field("prefix", &self.prefix)
+            .field("stop", &self.stop)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MqttPublisher {
+    pub(crate) fn publish(&self, devices: &[TRDevice]) {
+        for device in devices {
+            let Ok(payload) = serde_json::to_vec(device) else {
+                continue;
+            };
+
+            // never block the poll loop on a slow or missing broker
+            let _ = self.client.try_publish(
+                format!("{}/{}", self.prefix, device.address),
+                QoS::AtMostOnce,
+                false,
+                payload,
+            );
+        }
+    }
+
+    fn disconnect(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.client.try_disconnect();
+    }
+}
+
+fn poll_connection(app: AppHandle, mut connection: Connection, stop: Arc<AtomicBool>) {
+    for event in connection.iter() {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Err(err) = event {
+            send_warn(app.clone(), format!("mqtt connection error: {}", err));
+            sleep(RETRY_DELAY);
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn mqtt_connect(app: AppHandle, broker: String, topic_prefix: String) {
+    let (host, port) = match broker.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host.to_string(), port),
+            Err(_) => {
+                send_error(app, format!("invalid mqtt broker port: {}", broker));
+                return;
+            }
+        },
+        None => (broker.clone(), DEFAULT_MQTT_PORT),
+    };
+
+    let mut options = MqttOptions::new(format!("mmvisual-{}", std::process::id()), host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+
+    let (client, connection) = Client::new(options, 100);
+
+    let publisher = MqttPublisher {
+        client,
+        prefix: topic_prefix.trim_end_matches('/').to_string(),
+        stop: Arc::new(AtomicBool::new(false)),
+    };
+
+    // the blocking connection drives its own runtime, so it can't run on
+    // the async runtime
+    thread::spawn({
+        let app = app.clone();
+        let stop = publisher.stop.clone();
+        move || poll_connection(app, connection, stop)
+    });
+
+    let mut state = lock_state(&app);
+
+    if let Some(publisher) = state.mqtt.take() {
+        publisher.disconnect();
+    }
+    state.mqtt = Some(publisher);
+}
+
+#[tauri::command]
+pub(crate) fn mqtt_disconnect(app: AppHandle) {
+    let mut state = lock_state(&app);
+
+    if let Some(publisher) = state.mqtt.take() {
+        publisher.disconnect();
+    }
+}
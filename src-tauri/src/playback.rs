@@ -0,0 +1,359 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
+
+use tauri::{AppHandle, Emitter};
+
+use crate::{
+    display_devices, lock_state,
+    record::{read_annotations, read_samples, Annotation, Sample},
+    send_devices, send_error, send_warn, TRDevice,
+};
+
+const MIN_SPEED: f64 = 0.1;
+const MAX_SPEED: f64 = 100.0;
+const WAIT_SLICE: Duration = Duration::from_millis(50);
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+const MAX_INTERPOLATION_GAP_MS: u64 = 2000;
+
+fn apply(devices: &mut Vec<TRDevice>, sample: &Sample) {
+    if let Some(device) = devices.iter_mut().find(|d| d.address == sample.address) {
+        *device = sample.to_device();
+    } else {
+        devices.push(sample.to_device());
+    }
+}
+
+/// Returns the index of the first sample at or after `t`, along with the
+/// state of every device at `t`.
+fn seek(samples: &[Sample], t: u64) -> (usize, Vec<TRDevice>) {
+    let mut devices = Vec::<TRDevice>::new();
+    for sample in samples.iter().take_while(|s| s.t <= t) {
+        apply(&mut devices, sample);
+    }
+
+    (samples.partition_point(|s| s.t < t), devices)
+}
+
+/// Sleeps for `duration`, waking early if playback is stopped or a seek is
+/// requested.
+fn wait(app: &AppHandle, stop: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+
+    while !stop.load(Ordering::Relaxed) && lock_state(app).playback_seek.is_none() {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        sleep((deadline - now).min(WAIT_SLICE));
+    }
+}
+
+/// Where each device's previous and next samples are, for drawing positions
+/// in between them.
+struct Interpolation {
+    next: Vec<Option<usize>>,
+    last: HashMap<u8, usize>,
+}
+
+impl Interpolation {
+    fn new(samples: &[Sample]) -> Interpolation {
+        let mut next = vec![None; samples.len()];
+        let mut later = HashMap::<u8, usize>::new();
+        for (index, sample) in samples.iter().enumerate().rev() {
+            next[index] = later.insert(sample.address, index);
+        }
+
+        Interpolation {
+            next,
+            last: HashMap::new(),
+        }
+    }
+
+    fn seek(&mut self, samples: &[Sample], t: u64) {
+        self.last.clear();
+        for (index, sample) in samples.iter().enumerate().take_while(|(_, s)| s.t <= t) {
+            self.last.insert(sample.address, index);
+        }
+    }
+
+    /// Returns `devices` moved along to where they are at `t`, leaving
+    /// devices in place across gaps longer than `MAX_INTERPOLATION_GAP_MS`.
+    fn frame(&self, samples: &[Sample], devices: &[TRDevice], t: f64) -> Vec<TRDevice> {
+        devices
+            .iter()
+            .map(|device| {
+                let mut device = device.clone();
+
+                let Some(&index) = self.last.get(&device.address) else {
+                    return device;
+                };
+                let Some(next) = self.next[index] else {
+                    return device;
+                };
+
+                let (a, b) = (&samples[index], &samples[next]);
+                let gap_ms = b.t.saturating_sub(a.t);
+                if gap_ms == 0 || gap_ms > MAX_INTERPOLATION_GAP_MS {
+                    return device;
+                }
+
+                let f = ((t - a.t as f64) / gap_ms as f64).clamp(0.0, 1.0);
+                device.x = a.x + (b.x - a.x) * f;
+                device.y = a.y + (b.y - a.y) * f;
+                device.z = a.z + (b.z - a.z) * f;
+                device
+            })
+            .collect()
+    }
+}
+
+/// Sends interpolated positions every `FRAME_INTERVAL` over the recording
+/// times in `span`, returning early if playback is stopped or a seek is
+/// requested.
+fn render_frames(
+    app: &AppHandle,
+    stop: &AtomicBool,
+    interpolation: &Interpolation,
+    samples: &[Sample],
+    devices: &[TRDevice],
+    span: Range<u64>,
+    speed: f64,
+) {
+    let start = Instant::now();
+
+    loop {
+        let t = span.start as f64 + start.elapsed().as_secs_f64() * 1000.0 * speed;
+        if t >= span.end as f64 {
+            return;
+        }
+
+        let frame = interpolation.frame(samples, devices, t);
+        send_devices(app, &lock_state(app), &frame);
+
+        let remaining = Duration::from_secs_f64((span.end as f64 - t) / 1000.0 / speed);
+        wait(app, stop, FRAME_INTERVAL.min(remaining));
+
+        if stop.load(Ordering::Relaxed) || lock_state(app).playback_seek.is_some() {
+            return;
+        }
+    }
+}
+
+/// Emits every annotation from `next` up to and including `t`, returning the
+/// index of the first one left.
+fn emit_annotations(app: &AppHandle, annotations: &[Annotation], next: usize, t: u64) -> usize {
+    let end = next + annotations[next..].partition_point(|a| a.t <= t);
+    for annotation in &annotations[next..end] {
+        app.emit("annotation", annotation).unwrap();
+    }
+
+    end
+}
+
+fn playback(
+    app: AppHandle,
+    samples: Vec<Sample>,
+    annotations: Vec<Annotation>,
+    interpolate: bool,
+    stop: Arc<AtomicBool>,
+) {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        app.emit("playback-finished", ()).unwrap();
+        return;
+    };
+    let range = first.t..=last.t;
+
+    let mut devices = Vec::<TRDevice>::new();
+    let mut prev_t = first.t;
+    let mut index = 0;
+    let mut next_annotation = 0;
+    let mut interpolation = interpolate.then(|| Interpolation::new(&samples));
+
+    while index < samples.len() {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some(t) = lock_state(&app).playback_seek.take() {
+            let t = t.clamp(*range.start(), *range.end());
+            (index, devices) = seek(&samples, t);
+            prev_t = t;
+            // annotations before the seek target are skipped, not replayed
+            next_annotation = annotations.partition_point(|a| a.t < t);
+            if let Some(interpolation) = &mut interpolation {
+                interpolation.seek(&samples, t);
+            }
+
+            send_devices(&app, &lock_state(&app), &devices);
+            continue;
+        }
+
+        let sample = &samples[index];
+
+        // flush every sample sharing the previous timestamp before waiting
+        if sample.t > prev_t {
+            send_devices(&app, &lock_state(&app), &devices);
+
+            let speed = lock_state(&app).playback_speed;
+            match &interpolation {
+                Some(interpolation) => render_frames(
+                    &app,
+                    &stop,
+                    interpolation,
+                    &samples,
+                    &devices,
+                    prev_t..sample.t,
+                    speed,
+                ),
+                None => wait(
+                    &app,
+                    &stop,
+                    Duration::from_secs_f64((sample.t - prev_t) as f64 / 1000.0 / speed),
+                ),
+            }
+
+            // a seek while waiting replaces this sample
+            if lock_state(&app).playback_seek.is_some() {
+                continue;
+            }
+            prev_t = sample.t;
+        }
+
+        next_annotation = emit_annotations(&app, &annotations, next_annotation, prev_t);
+
+        apply(&mut devices, sample);
+        if let Some(interpolation) = &mut interpolation {
+            interpolation.last.insert(sample.address, index);
+        }
+        index += 1;
+    }
+
+    send_devices(&app, &lock_state(&app), &devices);
+    emit_annotations(&app, &annotations, next_annotation, u64::MAX);
+    app.emit("playback-finished", ()).unwrap();
+}
+
+/// Plays back the recording at `path`. With `interpolate`, positions are
+/// drawn moving between samples instead of jumping from one to the next.
+#[tauri::command]
+pub(crate) fn start_playback(
+    app: AppHandle,
+    path: String,
+    speed: Option<f64>,
+    interpolate: Option<bool>,
+) {
+    let samples = match read_samples(Path::new(&path)) {
+        Ok(samples) => samples,
+        Err(err) => {
+            send_error(app, format!("failed to read recording: {:#}", err));
+            return;
+        }
+    };
+
+    samples.warn(&app, Path::new(&path));
+
+    let annotations = match read_annotations(Path::new(&path)) {
+        Ok(annotations) => annotations,
+        Err(err) => {
+            send_warn(
+                app.clone(),
+                format!("failed to read annotations: {:#}", err),
+            );
+            Vec::new()
+        }
+    };
+
+    let mut state = lock_state(&app);
+
+    state.playback_stop.store(true, Ordering::Relaxed);
+    state.playback_stop = Arc::new(AtomicBool::new(false));
+    state.playback_speed = clamp_speed(speed.unwrap_or(1.0));
+    state.playback_seek = None;
+
+    thread::spawn({
+        let app = app.clone();
+        let stop = state.playback_stop.clone();
+        move || {
+            playback(
+                app,
+                samples.samples,
+                annotations,
+                interpolate.unwrap_or(false),
+                stop,
+            )
+        }
+    });
+}
+
+/// Returns the last known position and quality of every device in a
+/// recording, for showing its final state without playing it back.
+#[tauri::command]
+pub(crate) fn load_recording(app: AppHandle, path: String) -> Vec<TRDevice> {
+    let samples = match read_samples(Path::new(&path)) {
+        Ok(samples) => samples,
+        Err(err) => {
+            send_error(app, format!("failed to read recording: {:#}", err));
+            return Vec::new();
+        }
+    };
+
+    samples.warn(&app, Path::new(&path));
+
+    let mut devices = Vec::<TRDevice>::new();
+    for sample in samples.samples {
+        match devices.iter_mut().find(|d| d.address == sample.address) {
+            Some(device) if device.last_update_ms > sample.t => {}
+            Some(device) => *device = sample.to_device(),
+            None => devices.push(sample.to_device()),
+        }
+    }
+    devices.sort_by_key(|d| d.address);
+
+    display_devices(&lock_state(&app), &devices)
+}
+
+#[tauri::command]
+pub(crate) fn stop_playback(app: AppHandle) {
+    let state = lock_state(&app);
+
+    state.playback_stop.store(true, Ordering::Relaxed);
+}
+
+/// Moves playback to the first sample at or after `t_ms`, clamped to the
+/// recording, and emits the device state at that instant before resuming.
+#[tauri::command]
+pub(crate) fn playback_seek(app: AppHandle, t_ms: u64) {
+    let mut state = lock_state(&app);
+
+    state.playback_seek = Some(t_ms);
+}
+
+#[tauri::command]
+pub(crate) fn set_playback_speed(app: AppHandle, speed: f64) {
+    let mut state = lock_state(&app);
+
+    state.playback_speed = clamp_speed(speed);
+}
+
+fn clamp_speed(speed: f64) -> f64 {
+    if speed.is_nan() {
+        return 1.0;
+    }
+
+    speed.clamp(MIN_SPEED, MAX_SPEED)
+}
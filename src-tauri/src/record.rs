@@ -0,0 +1,810 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Error, Result};
+use tauri::AppHandle;
+
+use crate::{send_warn, unix_millis, Location, TRDevice};
+
+pub(crate) const DELIMITERS: [char; 3] = [',', ';', '\t'];
+const ROW_ESTIMATE_BYTES: u64 = 64 * 1024;
+const ANNOTATION_MARKER: &str = "# annotation ";
+const TIME_SOURCE_MARKER: &str = "# timestamps ";
+// written by `set_record_paused`
+const PAUSE_MARKERS: [&str; 2] = ["# paused at ", "# resumed at "];
+
+/// Where the `t` of recorded rows comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TimeSource {
+    /// The update time the modem reports with each fix.
+    Modem,
+    /// This computer's clock, for simulated fixes and beacon snapshots.
+    Host,
+}
+
+impl TimeSource {
+    fn name(&self) -> &'static str {
+        match self {
+            TimeSource::Modem => "modem",
+            TimeSource::Host => "host",
+        }
+    }
+}
+
+/// A column of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RecordField {
+    Address,
+    X,
+    Y,
+    Z,
+    Q,
+    T,
+    IsHedge,
+}
+
+impl RecordField {
+    pub(crate) const ALL: [RecordField; 7] = [
+        RecordField::Address,
+        RecordField::X,
+        RecordField::Y,
+        RecordField::Z,
+        RecordField::Q,
+        RecordField::T,
+        RecordField::IsHedge,
+    ];
+
+    pub(crate) const DEFAULT: [RecordField; 6] = [
+        RecordField::Address,
+        RecordField::X,
+        RecordField::Y,
+        RecordField::Z,
+        RecordField::Q,
+        RecordField::T,
+    ];
+
+    pub(crate) fn parse(name: &str) -> Option<RecordField> {
+        RecordField::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            RecordField::Address => "address",
+            RecordField::X => "x",
+            RecordField::Y => "y",
+            RecordField::Z => "z",
+            RecordField::Q => "q",
+            RecordField::T => "t",
+            RecordField::IsHedge => "is_hedge",
+        }
+    }
+
+    fn format(&self, location: &Location) -> String {
+        match self {
+            RecordField::Address => location.address.to_string(),
+            RecordField::X => ((location.x * 1000.0).round() as i64).to_string(),
+            RecordField::Y => ((location.y * 1000.0).round() as i64).to_string(),
+            RecordField::Z => ((location.z * 1000.0).round() as i64).to_string(),
+            RecordField::Q => location.q.to_string(),
+            RecordField::T => location.t.to_string(),
+            RecordField::IsHedge => (location.is_hedge as u8).to_string(),
+        }
+    }
+}
+
+/// A single row of a recording, with coordinates converted to meters.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Sample {
+    pub(crate) address: u8,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) z: f64,
+    pub(crate) q: u8,
+    pub(crate) t: u64,
+    pub(crate) is_hedge: bool,
+}
+
+impl Sample {
+    pub(crate) fn to_device(&self) -> TRDevice {
+        TRDevice {
+            address: self.address,
+            is_hedge: self.is_hedge,
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            q: self.q,
+            last_update_ms: self.t,
+            ..Default::default()
+        }
+    }
+}
+
+/// An open recording, along with what has been written to it so far.
+#[derive(Debug)]
+pub(crate) struct Recording {
+    file: File,
+    path: PathBuf,
+    fields: Vec<RecordField>,
+    delimiter: char,
+    rows: usize,
+    addresses: HashSet<u8>,
+    first_t: Option<u64>,
+    last_t: u64,
+    bytes: u64,
+    started: Instant,
+    max_duration: Option<Duration>,
+    max_bytes: Option<u64>,
+    time_source: Option<TimeSource>,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct RecordSummary {
+    path: String,
+    rows: usize,
+    addresses: usize,
+    duration_ms: u64,
+}
+
+impl Recording {
+    /// Creates a recording at `path`. With `append`, rows are added to an
+    /// existing non-empty file instead, whose header `header_mismatch` can
+    /// check beforehand.
+    pub(crate) fn create(
+        path: PathBuf,
+        fields: Vec<RecordField>,
+        delimiter: char,
+        append: bool,
+    ) -> Result<Recording, Error> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)
+                    .context(format!("failed to create directory: {}", parent.display()))?;
+            }
+        }
+
+        let header = header(&fields, delimiter);
+        let is_existing = append && is_non_empty(&path);
+
+        let file = if is_existing {
+            OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .context(format!("failed to open file: {}", path.display()))?
+        } else {
+            let mut file = File::create(&path)
+                .context(format!("failed to create file: {}", path.display()))?;
+            file.write_all(format!("{}\n", header).as_bytes())?;
+            file
+        };
+
+        let bytes = file.metadata()?.len();
+
+        Ok(Recording {
+            file,
+            path,
+            fields,
+            delimiter,
+            rows: 0,
+            addresses: HashSet::<u8>::new(),
+            first_t: None,
+            last_t: 0,
+            bytes,
+            started: Instant::now(),
+            max_duration: None,
+            max_bytes: None,
+            time_source: None,
+        })
+    }
+
+    /// Sets when `is_limit_reached` reports the recording as full, where
+    /// `None` leaves it unlimited. The file size includes rows appended to.
+    pub(crate) fn set_limits(&mut self, max_duration_ms: Option<u64>, max_bytes: Option<u64>) {
+        self.max_duration = max_duration_ms.map(Duration::from_millis);
+        self.max_bytes = max_bytes;
+    }
+
+    pub(crate) fn is_limit_reached(&self) -> bool {
+        self.max_duration
+            .is_some_and(|max_duration| self.started.elapsed() >= max_duration)
+            || self
+                .max_bytes
+                .is_some_and(|max_bytes| self.bytes >= max_bytes)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a row, returning how many bytes were written. Whenever the
+    /// rows start coming from another clock, a `# timestamps modem` or
+    /// `# timestamps host` comment row is written before it.
+    pub(crate) fn write(
+        &mut self,
+        location: &Location,
+        source: TimeSource,
+    ) -> Result<usize, Error> {
+        let row: Vec<String> = self.fields.iter().map(|f| f.format(location)).collect();
+        let mut line = format!("{}\n", row.join(&self.delimiter.to_string()));
+        if self.time_source != Some(source) {
+            line.insert_str(0, &format!("{}{}\n", TIME_SOURCE_MARKER, source.name()));
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.time_source = Some(source);
+
+        self.bytes += line.len() as u64;
+        self.rows += 1;
+        self.addresses.insert(location.address);
+        self.first_t.get_or_insert(location.t);
+        self.last_t = self.last_t.max(location.t);
+
+        Ok(line.len())
+    }
+
+    /// Writes a `#` comment row, which readers skip.
+    pub(crate) fn write_marker(&mut self, text: &str) -> Result<(), Error> {
+        self.file.write_all(format!("# {}\n", text).as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Writes an annotation as a comment row, so it is skipped as a sample
+    /// but can still be found by `read_annotations`.
+    pub(crate) fn write_annotation(&mut self, t: u64, label: &str) -> Result<(), Error> {
+        let label = label.replace(['\r', '\n'], " ");
+        self.file
+            .write_all(format!("{}{} {}\n", ANNOTATION_MARKER, t, label.trim()).as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Flushes written rows all the way to disk.
+    pub(crate) fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+
+        Ok(())
+    }
+
+    pub(crate) fn summary(&self) -> RecordSummary {
+        RecordSummary {
+            path: self.path.to_string_lossy().into(),
+            rows: self.rows,
+            addresses: self.addresses.len(),
+            duration_ms: self
+                .first_t
+                .map(|first_t| self.last_t.saturating_sub(first_t))
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Recording {
+    /// Creates a recording whose file is reopened read-only, so every write
+    /// to it fails.
+    pub(crate) fn read_only(path: PathBuf) -> Result<Recording, Error> {
+        let mut recording = Recording::create(path, RecordField::DEFAULT.to_vec(), ',', false)?;
+        recording.file = File::open(&recording.path)?;
+
+        Ok(recording)
+    }
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct RecordingInfo {
+    path: String,
+    size_bytes: u64,
+    modified: u64,
+    approx_rows: u64,
+}
+
+impl RecordingInfo {
+    pub(crate) fn modified(&self) -> u64 {
+        self.modified
+    }
+
+    /// Describes the recording at `path`, estimating its rows from the line
+    /// lengths in its first `ROW_ESTIMATE_BYTES`.
+    pub(crate) fn read(path: &Path) -> Result<RecordingInfo, Error> {
+        let metadata = std::fs::metadata(path)
+            .context(format!("failed to read metadata: {}", path.display()))?;
+
+        let mut head = Vec::<u8>::new();
+        File::open(path)
+            .context(format!("failed to open file: {}", path.display()))?
+            .take(ROW_ESTIMATE_BYTES)
+            .read_to_end(&mut head)?;
+
+        let lines = head.iter().filter(|&&b| b == b'\n').count() as u64;
+        let approx_rows = match lines {
+            0 => 0,
+            lines if head.len() as u64 == metadata.len() => lines - 1,
+            lines => (metadata.len() * lines / head.len() as u64).saturating_sub(1),
+        };
+
+        Ok(RecordingInfo {
+            path: path.to_string_lossy().into(),
+            size_bytes: metadata.len(),
+            modified: metadata.modified().map(unix_millis).unwrap_or(0),
+            approx_rows,
+        })
+    }
+}
+
+fn header(fields: &[RecordField], delimiter: char) -> String {
+    let header: Vec<&str> = fields.iter().map(|f| f.name()).collect();
+    header.join(&delimiter.to_string())
+}
+
+fn is_non_empty(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| m.len() > 0)
+}
+
+/// Returns the header of the existing non-empty file at `path` when it
+/// doesn't match `fields` written with `delimiter`, as appending to it would
+/// put rows under the wrong columns.
+pub(crate) fn header_mismatch(
+    path: &Path,
+    fields: &[RecordField],
+    delimiter: char,
+) -> Result<Option<String>, Error> {
+    if !is_non_empty(path) {
+        return Ok(None);
+    }
+
+    let existing = BufReader::new(open(path)?)
+        .lines()
+        .next()
+        .transpose()?
+        .unwrap_or_default();
+    let existing = existing.trim_end();
+
+    Ok((existing != header(fields, delimiter)).then(|| existing.to_string()))
+}
+
+fn open(path: &Path) -> Result<File, Error> {
+    File::open(path).context(format!("failed to open file: {}", path.display()))
+}
+
+/// Returns the delimiter used by a recording, judging by its header.
+pub(crate) fn read_delimiter(path: &Path) -> Result<u8, Error> {
+    let mut header = String::new();
+    BufReader::new(open(path)?).read_line(&mut header)?;
+
+    let delimiter = DELIMITERS
+        .into_iter()
+        .find(|&delimiter| header.contains(delimiter))
+        .unwrap_or(',');
+
+    Ok(delimiter as u8)
+}
+
+/// Opens a recording as CSV, with the delimiter taken from its header and
+/// `#` comment rows skipped.
+pub(crate) fn csv_reader(path: &Path) -> Result<csv::Reader<File>, Error> {
+    Ok(csv::ReaderBuilder::new()
+        .delimiter(read_delimiter(path)?)
+        .comment(Some(b'#'))
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(open(path)?))
+}
+
+/// Where each sample field is found in the rows of a recording.
+#[derive(Debug, Default)]
+struct Columns {
+    address: usize,
+    x: Option<usize>,
+    y: Option<usize>,
+    z: Option<usize>,
+    q: Option<usize>,
+    t: Option<usize>,
+    is_hedge: Option<usize>,
+}
+
+impl Columns {
+    /// Maps the recognized columns of a header by name, returning them along
+    /// with the names of any unrecognized ones.
+    fn from_header(header: &csv::StringRecord) -> Result<(Columns, Vec<String>), Error> {
+        let position = |name: &str| header.iter().position(|column| column == name);
+
+        let columns = Columns {
+            address: position(RecordField::Address.name()).context("no column: address")?,
+            x: position(RecordField::X.name()),
+            y: position(RecordField::Y.name()),
+            z: position(RecordField::Z.name()),
+            q: position(RecordField::Q.name()),
+            t: position(RecordField::T.name()),
+            is_hedge: position(RecordField::IsHedge.name()),
+        };
+
+        let unknown = header
+            .iter()
+            .filter(|&column| RecordField::parse(column).is_none())
+            .map(|column| column.to_string())
+            .collect();
+
+        Ok((columns, unknown))
+    }
+
+    /// Parses a row, filling missing fields other than the address with
+    /// their defaults. Rows without `is_hedge` are taken as hedgehogs, since
+    /// only those are recorded unless beacons are asked for.
+    fn parse(&self, record: &csv::StringRecord) -> Result<Sample, Error> {
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+        let meters = |column: Option<usize>| -> Result<f64, Error> {
+            match field(column) {
+                Some(value) => Ok(value.parse::<f64>()? / 1000.0),
+                None => Ok(0.0),
+            }
+        };
+
+        Ok(Sample {
+            address: field(Some(self.address))
+                .context("no value: address")?
+                .parse::<u8>()?,
+            x: meters(self.x)?,
+            y: meters(self.y)?,
+            z: meters(self.z)?,
+            q: field(self.q)
+                .map(str::parse::<u8>)
+                .transpose()?
+                .unwrap_or(0),
+            t: field(self.t)
+                .map(str::parse::<u64>)
+                .transpose()?
+                .unwrap_or(0),
+            is_hedge: field(self.is_hedge).map(str::parse::<u8>).transpose()? != Some(0),
+        })
+    }
+}
+
+/// Streams the samples of a recording. Malformed rows are yielded as
+/// `Ok(None)` so callers can skip and count them, while I/O errors are
+/// returned as `Err`.
+pub(crate) struct SampleReader {
+    records: csv::StringRecordsIntoIter<File>,
+    columns: Columns,
+    unknown_columns: Vec<String>,
+}
+
+impl SampleReader {
+    pub(crate) fn unknown_columns(&self) -> &[String] {
+        &self.unknown_columns
+    }
+}
+
+impl Iterator for SampleReader {
+    type Item = Result<Option<Sample>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.records.next()? {
+            Ok(record) => Some(Ok(self.columns.parse(&record).ok())),
+            Err(err) if err.is_io_error() => Some(Err(err.into())),
+            Err(_) => Some(Ok(None)),
+        }
+    }
+}
+
+/// Opens a recording for streaming its samples, with columns mapped by the
+/// names in its header.
+pub(crate) fn sample_reader(path: &Path) -> Result<SampleReader, Error> {
+    let mut reader = csv_reader(path)?;
+    let (columns, unknown_columns) = Columns::from_header(reader.headers()?)?;
+
+    Ok(SampleReader {
+        records: reader.into_records(),
+        columns,
+        unknown_columns,
+    })
+}
+
+/// The samples of a recording, along with what was skipped reading them.
+#[derive(Debug, Default)]
+pub(crate) struct Samples {
+    pub(crate) samples: Vec<Sample>,
+    skipped: usize,
+    unknown_columns: Vec<String>,
+}
+
+impl Samples {
+    /// Logs any malformed rows and unrecognized columns.
+    pub(crate) fn warn(&self, app: &AppHandle, path: &Path) {
+        warn_read_issues(app, path, self.skipped, &self.unknown_columns);
+    }
+}
+
+pub(crate) fn warn_read_issues(
+    app: &AppHandle,
+    path: &Path,
+    skipped: usize,
+    unknown_columns: &[String],
+) {
+    if skipped > 0 {
+        send_warn(
+            app.clone(),
+            format!("skipped {} malformed rows in {}", skipped, path.display()),
+        );
+    }
+
+    if !unknown_columns.is_empty() {
+        send_warn(
+            app.clone(),
+            format!(
+                "ignored unknown columns in {}: {}",
+                path.display(),
+                unknown_columns.join(", ")
+            ),
+        );
+    }
+}
+
+/// Reads every sample of a recording ordered by time, skipping malformed
+/// rows. Rows of appended or merged recordings may be out of order on disk,
+/// and those with the same time keep their order.
+pub(crate) fn read_samples(path: &Path) -> Result<Samples, Error> {
+    let mut reader = sample_reader(path)?;
+    let mut samples = Samples::default();
+
+    for sample in &mut reader {
+        match sample? {
+            Some(sample) => samples.samples.push(sample),
+            None => samples.skipped += 1,
+        }
+    }
+    samples.unknown_columns = reader.unknown_columns;
+    samples.samples.sort_by_key(|sample| sample.t);
+
+    Ok(samples)
+}
+
+/// A labelled point in time of a recording.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Annotation {
+    pub(crate) t: u64,
+    label: String,
+}
+
+/// Reads every annotation of the recording at `path`, ordered by time.
+pub(crate) fn read_annotations(path: &Path) -> Result<Vec<Annotation>, Error> {
+    let mut annotations = Vec::<Annotation>::new();
+
+    for line in BufReader::new(open(path)?).lines() {
+        let line = line?;
+        let Some(rest) = line.strip_prefix(ANNOTATION_MARKER) else {
+            continue;
+        };
+        let (t, label) = rest.split_once(' ').unwrap_or((rest, ""));
+        let Ok(t) = t.parse::<u64>() else {
+            continue;
+        };
+
+        annotations.push(Annotation {
+            t,
+            label: label.trim().into(),
+        });
+    }
+
+    annotations.sort_by_key(|a| a.t);
+
+    Ok(annotations)
+}
+
+/// A `#` comment row of a recording, which `csv_reader` skips.
+#[derive(Debug, Clone)]
+pub(crate) struct Comment {
+    /// How many data rows come before it.
+    pub(crate) row: usize,
+    pub(crate) text: String,
+}
+
+impl Comment {
+    /// Whether this marks the clock of the rows after it.
+    pub(crate) fn is_time_source(&self) -> bool {
+        self.text.starts_with(TIME_SOURCE_MARKER)
+    }
+
+    /// The time an annotation or a pause marker was written at.
+    pub(crate) fn t(&self) -> Option<u64> {
+        let rest = std::iter::once(ANNOTATION_MARKER)
+            .chain(PAUSE_MARKERS)
+            .find_map(|marker| self.text.strip_prefix(marker))?;
+
+        rest.split(' ').next()?.parse().ok()
+    }
+}
+
+/// Reads every comment row of the recording at `path`, in file order.
+pub(crate) fn read_comments(path: &Path) -> Result<Vec<Comment>, Error> {
+    let mut comments = Vec::<Comment>::new();
+    let mut lines = 0;
+
+    for line in BufReader::new(open(path)?).lines() {
+        let line = line?;
+        if line.starts_with('#') {
+            comments.push(Comment {
+                // the header is not a data row
+                row: lines.max(1) - 1,
+                text: line,
+            });
+        } else if !line.is_empty() {
+            lines += 1;
+        }
+    }
+
+    Ok(comments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(header: &[&str]) -> (Columns, Vec<String>) {
+        Columns::from_header(&csv::StringRecord::from(header.to_vec())).unwrap()
+    }
+
+    fn sample(columns: &Columns, row: &[&str]) -> Sample {
+        columns
+            .parse(&csv::StringRecord::from(row.to_vec()))
+            .unwrap()
+    }
+
+    #[test]
+    fn beacon_rows_keep_is_hedge() {
+        let (columns, _) = columns(&["address", "x", "t", "is_hedge"]);
+
+        assert!(
+            sample(&columns, &["3", "0", "10", "1"])
+                .to_device()
+                .is_hedge
+        );
+        assert!(
+            !sample(&columns, &["5", "0", "10", "0"])
+                .to_device()
+                .is_hedge
+        );
+    }
+
+    #[test]
+    fn rows_without_is_hedge_are_hedgehogs() {
+        let (columns, _) = columns(&["address", "x", "t"]);
+
+        assert!(sample(&columns, &["3", "0", "10"]).is_hedge);
+    }
+
+    #[test]
+    fn columns_are_mapped_by_name() {
+        let (columns, unknown) = columns(&["t", "q", "y", "x", "address", "battery"]);
+        let sample = sample(&columns, &["10", "90", "2500", "-1500", "3", "77"]);
+
+        assert_eq!(sample.address, 3);
+        assert_eq!(sample.x, -1.5);
+        assert_eq!(sample.y, 2.5);
+        assert_eq!(sample.q, 90);
+        assert_eq!(sample.t, 10);
+        assert_eq!(unknown, ["battery"]);
+    }
+
+    #[test]
+    fn missing_columns_take_defaults() {
+        let (columns, unknown) = columns(&["address", "x", "y"]);
+        let sample = sample(&columns, &["3", "1000", ""]);
+
+        assert_eq!(sample.x, 1.0);
+        assert_eq!(sample.y, 0.0);
+        assert_eq!(sample.z, 0.0);
+        assert_eq!(sample.t, 0);
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn address_column_is_required() {
+        let header = csv::StringRecord::from(vec!["x", "y", "t"]);
+
+        assert!(Columns::from_header(&header).is_err());
+    }
+
+    #[test]
+    fn malformed_values_are_errors() {
+        let (columns, _) = columns(&["address", "x"]);
+
+        let row = |row: Vec<&str>| columns.parse(&csv::StringRecord::from(row));
+        assert!(row(vec!["3", "east"]).is_err());
+        assert!(row(vec!["300", "0"]).is_err());
+        assert!(row(vec!["", "0"]).is_err());
+    }
+
+    #[test]
+    fn time_source_is_marked_when_it_changes() {
+        let path =
+            std::env::temp_dir().join(format!("mmvisual-time-source-{}.csv", std::process::id()));
+        let mut recording = Recording::create(
+            path.clone(),
+            vec![RecordField::Address, RecordField::T],
+            ',',
+            false,
+        )
+        .unwrap();
+
+        let location = |t| Location {
+            address: 3,
+            t,
+            ..Default::default()
+        };
+        recording.write(&location(1), TimeSource::Host).unwrap();
+        recording.write(&location(2), TimeSource::Modem).unwrap();
+        recording.write(&location(3), TimeSource::Modem).unwrap();
+        recording.flush().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "address,t\n# timestamps host\n3,1\n# timestamps modem\n3,2\n3,3\n"
+        );
+        assert_eq!(read_samples(&path).unwrap().samples.len(), 3);
+    }
+
+    #[test]
+    fn append_continues_after_a_header_mismatch() {
+        let path = std::env::temp_dir().join(format!("mmvisual-append-{}.csv", std::process::id()));
+        std::fs::write(&path, "address,x,t\n3,0,1\n").unwrap();
+        let fields = vec![RecordField::Address, RecordField::T];
+
+        assert_eq!(
+            header_mismatch(&path, &fields, ',').unwrap().as_deref(),
+            Some("address,x,t")
+        );
+        assert_eq!(
+            header_mismatch(
+                &path,
+                &[RecordField::Address, RecordField::X, RecordField::T],
+                ','
+            )
+            .unwrap(),
+            None
+        );
+
+        let mut recording = Recording::create(path.clone(), fields, ',', true).unwrap();
+        recording
+            .write(
+                &Location {
+                    address: 5,
+                    t: 2,
+                    ..Default::default()
+                },
+                TimeSource::Modem,
+            )
+            .unwrap();
+        recording.flush().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "address,x,t\n3,0,1\n# timestamps modem\n5,2\n"
+        );
+    }
+
+    #[test]
+    fn samples_are_read_in_time_order() {
+        let path =
+            std::env::temp_dir().join(format!("mmvisual-unordered-{}.csv", std::process::id()));
+        std::fs::write(&path, "address,t\n1,20\n2,10\n3,20\n4,5\n").unwrap();
+
+        let samples = read_samples(&path).unwrap().samples;
+        let order: Vec<(u8, u64)> = samples.iter().map(|s| (s.address, s.t)).collect();
+        assert_eq!(order, [(4, 5), (2, 10), (1, 20), (3, 20)]);
+    }
+}
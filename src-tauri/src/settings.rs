@@ -0,0 +1,138 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{collections::HashMap, fs::File, io::BufWriter, path::Path};
+
+use anyhow::{Context, Error, Result};
+use tauri::AppHandle;
+
+use crate::{
+    lock_state, send_warn, AppState, Units, DEFAULT_EMIT_RATE_HZ, DEFAULT_LOST_TIMEOUT_MS,
+    DEFAULT_PORT, DEFAULT_QUALITY_ALERT, DEFAULT_TRAJECTORY_LIMIT,
+};
+
+pub(crate) const SETTINGS_NAME: &str = "settings.json";
+
+/// The user-facing settings kept across restarts. Missing keys fall back to
+/// their defaults, so older settings files keep loading.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    port: u32,
+    smoothing_window: usize,
+    max_jump_m: f64,
+    quality_threshold: u8,
+    quality_alerts: Vec<u8>,
+    units: Units,
+    origin_offset: (f64, f64),
+    lost_timeout_ms: u64,
+    trajectory_limit: usize,
+    trail_duration_ms: u64,
+    emit_rate_hz: f64,
+    colors: HashMap<u8, String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            port: DEFAULT_PORT,
+            smoothing_window: 1,
+            max_jump_m: 0.0,
+            quality_threshold: 0,
+            quality_alerts: vec![DEFAULT_QUALITY_ALERT],
+            units: Units::Meters,
+            origin_offset: (0.0, 0.0),
+            lost_timeout_ms: DEFAULT_LOST_TIMEOUT_MS,
+            trajectory_limit: DEFAULT_TRAJECTORY_LIMIT,
+            trail_duration_ms: 0,
+            emit_rate_hz: DEFAULT_EMIT_RATE_HZ,
+            colors: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub(crate) fn load(path: &Path) -> Result<Settings, Error> {
+        let file = File::open(path).context(format!("failed to open file: {}", path.display()))?;
+
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("failed to create directory: {}", parent.display()))?;
+        }
+
+        let file =
+            File::create(path).context(format!("failed to create file: {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+
+    fn from_state(state: &AppState) -> Settings {
+        Settings {
+            port: state.port,
+            smoothing_window: state.smoothing_window,
+            max_jump_m: state.max_jump_m,
+            quality_threshold: state.quality_threshold,
+            quality_alerts: state.quality_alerts.clone(),
+            units: state.units,
+            origin_offset: state.origin_offset,
+            lost_timeout_ms: state.lost_timeout_ms,
+            trajectory_limit: state.trajectory_limit,
+            trail_duration_ms: state.trail_duration_ms,
+            emit_rate_hz: state.emit_rate_hz,
+            colors: state.colors.clone(),
+        }
+    }
+
+    pub(crate) fn apply(&self, state: &mut AppState) {
+        state.port = self.port;
+        state.smoothing_window = self.smoothing_window.max(1);
+        state.max_jump_m = self.max_jump_m.max(0.0);
+        state.quality_threshold = self.quality_threshold;
+        state.quality_alerts = self.quality_alerts.clone();
+        state.units = self.units;
+        state.origin_offset = self.origin_offset;
+        state.lost_timeout_ms = self.lost_timeout_ms;
+        state.trajectory_limit = self.trajectory_limit;
+        state.trail_duration_ms = self.trail_duration_ms;
+        state.emit_rate_hz = self.emit_rate_hz.max(0.0);
+        state.colors = self.colors.clone();
+    }
+}
+
+/// Writes the current settings to the settings file, if there is one.
+pub(crate) fn save_settings(app: &AppHandle, state: &AppState) {
+    let Some(path) = &state.settings_path else {
+        return;
+    };
+
+    if let Err(err) = Settings::from_state(state).save(path) {
+        send_warn(app.clone(), format!("failed to save settings: {:#}", err));
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_settings(app: AppHandle) -> Settings {
+    let state = lock_state(&app);
+
+    Settings::from_state(&state)
+}
+
+/// Restores every setting to its default and saves the result.
+#[tauri::command]
+pub(crate) fn reset_settings(app: AppHandle) -> Settings {
+    let mut state = lock_state(&app);
+
+    let settings = Settings::default();
+    settings.apply(&mut state);
+    save_settings(&app, &state);
+
+    settings
+}
@@ -0,0 +1,162 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    f64::consts::TAU,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep},
+    time::{Duration, SystemTime},
+};
+
+use tauri::AppHandle;
+
+use crate::{
+    check_lost, check_quality, lock_state, record::TimeSource, record_location, send_devices,
+    send_log, send_warn, unix_millis, update_location, zone, EmitThrottle, Location, TRDevice,
+};
+
+const TICK: Duration = Duration::from_millis(50);
+const SIMULATED_QUALITY: u8 = 100;
+const SIMULATED_DTYPE: &str = "SimulatedHedgehog";
+
+/// Returns where simulated hedgehog `index` is `elapsed_s` seconds in, each
+/// one circling the origin on its own radius and period.
+fn simulated_location(index: usize, address: u8, elapsed_s: f64, t: u64) -> Location {
+    let radius = 1.0 + index as f64;
+    let period_s = 10.0 + 2.0 * index as f64;
+    let angle = TAU * elapsed_s / period_s + index as f64;
+
+    Location {
+        address,
+        is_hedge: true,
+        x: radius * angle.cos(),
+        y: radius * angle.sin(),
+        z: 0.0,
+        q: SIMULATED_QUALITY,
+        t,
+    }
+}
+
+/// Picks up to `num_devices` addresses that no real or INI device uses,
+/// reusing those of an earlier simulation first.
+fn free_addresses(devices: &[TRDevice], num_devices: usize) -> Vec<u8> {
+    (1..=u8::MAX)
+        .filter(|&address| {
+            devices
+                .iter()
+                .all(|d| d.address != address || d.dtype == SIMULATED_DTYPE)
+        })
+        .take(num_devices)
+        .collect()
+}
+
+fn simulate(app: AppHandle, addresses: Vec<u8>, stop: Arc<AtomicBool>) {
+    let start_t = unix_millis(SystemTime::now());
+    let mut throttle = EmitThrottle::default();
+
+    while !stop.load(Ordering::Relaxed) {
+        let t = unix_millis(SystemTime::now());
+        let elapsed_s = t.saturating_sub(start_t) as f64 / 1000.0;
+
+        let mut state = lock_state(&app);
+
+        for (index, &address) in addresses.iter().enumerate() {
+            let location = simulated_location(index, address, elapsed_s, t);
+
+            throttle.mark(update_location(&mut state, &location));
+            check_quality(&app, &mut state, &location);
+
+            if state.savefile.is_some() && !state.recording_paused {
+                record_location(&app, &mut state, &location, TimeSource::Host);
+            }
+        }
+
+        if throttle.take(state.emit_rate_hz) {
+            send_devices(&app, &state, &state.devices);
+        }
+
+        check_lost(&app, &mut state);
+        zone::check_zones(&app, &mut state);
+
+        drop(state);
+
+        sleep(TICK);
+    }
+}
+
+/// Feeds `num_devices` synthetic hedgehogs through the same path as live
+/// modem data, for working on the frontend without hardware.
+#[tauri::command]
+pub(crate) fn start_simulation(app: AppHandle, num_devices: usize) {
+    let mut state = lock_state(&app);
+
+    state.simulation_stop.store(true, Ordering::Relaxed);
+    state.simulation_stop = Arc::new(AtomicBool::new(false));
+
+    let addresses = free_addresses(&state.devices, num_devices);
+    if addresses.len() < num_devices {
+        send_warn(
+            app.clone(),
+            format!("only {} addresses free for simulation", addresses.len()),
+        );
+    }
+
+    for &address in &addresses {
+        if !state.devices.iter().any(|d| d.address == address) {
+            state.devices.push(TRDevice {
+                address,
+                dtype: SIMULATED_DTYPE.into(),
+                is_hedge: true,
+                ..Default::default()
+            });
+        }
+    }
+
+    send_log(
+        app.clone(),
+        format!("simulating {} hedgehogs", addresses.len()),
+    );
+
+    thread::spawn({
+        let app = app.clone();
+        let stop = state.simulation_stop.clone();
+        move || simulate(app, addresses, stop)
+    });
+}
+
+#[tauri::command]
+pub(crate) fn stop_simulation(app: AppHandle) {
+    let state = lock_state(&app);
+
+    state.simulation_stop.store(true, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(address: u8, dtype: &str) -> TRDevice {
+        TRDevice {
+            address,
+            dtype: dtype.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn simulation_skips_addresses_in_use() {
+        let devices = [
+            device(1, "Beacon"),
+            device(2, SIMULATED_DTYPE),
+            device(3, "BeaconHwV49Hedgehog"),
+        ];
+
+        assert_eq!(free_addresses(&devices, 3), [2, 4, 5]);
+    }
+}
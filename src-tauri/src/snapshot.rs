@@ -0,0 +1,182 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use anyhow::{Context, Error, Result};
+use image::{imageops::FilterType, ImageFormat, Rgba, RgbaImage};
+use tauri::AppHandle;
+
+use crate::{
+    lock_state,
+    record::{RecordField, Recording, TimeSource},
+    send_error, transform_point, unix_millis, AppState, Location,
+};
+
+const MARKER_RADIUS: i32 = 6;
+const GLYPH_SCALE: i32 = 2;
+const HEDGE_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BEACON_COLOR: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+// 3x5 bitmaps of the digits, one row per byte, so address labels can be
+// drawn without shipping a font
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+fn put(image: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+fn draw_marker(image: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>) {
+    for dy in -MARKER_RADIUS..=MARKER_RADIUS {
+        for dx in -MARKER_RADIUS..=MARKER_RADIUS {
+            if dx * dx + dy * dy <= MARKER_RADIUS * MARKER_RADIUS {
+                put(image, x + dx, y + dy, color);
+            }
+        }
+    }
+}
+
+fn draw_label(image: &mut RgbaImage, x: i32, y: i32, text: &str, color: Rgba<u8>) {
+    for (index, c) in text.chars().enumerate() {
+        let Some(digit) = c.to_digit(10) else {
+            continue;
+        };
+
+        let left = x + index as i32 * 4 * GLYPH_SCALE;
+
+        for (row, bits) in DIGITS[digit as usize].iter().enumerate() {
+            for col in 0..3 {
+                if (bits >> (2 - col)) & 1 == 0 {
+                    continue;
+                }
+
+                for sy in 0..GLYPH_SCALE {
+                    for sx in 0..GLYPH_SCALE {
+                        put(
+                            image,
+                            left + col * GLYPH_SCALE + sx,
+                            y + row as i32 * GLYPH_SCALE + sy,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the devices over the first floor image, scaled to `width` by
+/// `height` pixels.
+fn render(state: &AppState, width: u32, height: u32) -> Result<RgbaImage, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::msg("width and height must be at least 1"));
+    }
+
+    let plan = state.plan.as_ref().context("no map loaded")?;
+    let floor = plan.floors.first().context("no floor image")?;
+
+    let background = image::load_from_memory(&floor.data)
+        .context(format!("failed to decode image: {}", floor.file))?;
+    let scale_x = width as f64 / background.width() as f64;
+    let scale_y = height as f64 / background.height() as f64;
+
+    let mut image = background
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_rgba8();
+
+    for device in &state.devices {
+        // image rows grow downwards while floorplan y points up
+        let (x, y) = transform_point(plan, device.x, device.y);
+        let (x, y) = (x + state.origin_offset.0, y + state.origin_offset.1);
+        let x = (x * plan.scale_pixels_per_m * scale_x).round() as i32;
+        let y = (-y * plan.scale_pixels_per_m * scale_y).round() as i32;
+
+        let color = match device.is_hedge {
+            true => HEDGE_COLOR,
+            false => BEACON_COLOR,
+        };
+
+        draw_marker(&mut image, x, y, color);
+        draw_label(
+            &mut image,
+            x + MARKER_RADIUS + 2,
+            y - MARKER_RADIUS,
+            &device.address.to_string(),
+            LABEL_COLOR,
+        );
+    }
+
+    Ok(image)
+}
+
+/// Saves the floorplan with the current device positions and addresses
+/// drawn on it as a PNG.
+#[tauri::command]
+pub(crate) fn export_snapshot_png(
+    app: AppHandle,
+    path: String,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    let result = render(&lock_state(&app), width, height).and_then(|image| {
+        image
+            .save_with_format(&path, ImageFormat::Png)
+            .context(format!("failed to save image: {}", path))
+    });
+
+    result.map_err(|err| {
+        let msg = format!("failed to export snapshot: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
+
+/// Writes the current position of every device, stationary beacons
+/// included, as one recording row each stamped with the same time.
+fn write_csv(state: &AppState, path: PathBuf) -> Result<(), Error> {
+    let mut recording = Recording::create(path, RecordField::ALL.to_vec(), ',', false)?;
+    let t = unix_millis(SystemTime::now());
+
+    for device in &state.devices {
+        recording.write(
+            &Location {
+                address: device.address,
+                is_hedge: device.is_hedge,
+                x: device.x,
+                y: device.y,
+                z: device.z,
+                q: device.q,
+                t,
+            },
+            TimeSource::Host,
+        )?;
+    }
+
+    recording.flush()
+}
+
+#[tauri::command]
+pub(crate) fn export_snapshot_csv(app: AppHandle, path: String) -> Result<(), String> {
+    write_csv(&lock_state(&app), PathBuf::from(path)).map_err(|err| {
+        let msg = format!("failed to export snapshot: {:#}", err);
+        send_error(app.clone(), msg.clone());
+        msg
+    })
+}
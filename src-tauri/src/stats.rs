@@ -0,0 +1,411 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    collections::VecDeque,
+    path::Path,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use tauri::{AppHandle, Emitter};
+
+use crate::{
+    display_point, lock_state,
+    record::{read_samples, Sample},
+    send_error, transform_point,
+};
+
+const LOOP_WINDOW: usize = 1000;
+const THROUGHPUT_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_GRID_CELLS: f64 = 1_000_000.0;
+
+/// Tracks the time between successful location updates in `mmrun`, over the
+/// last `LOOP_WINDOW` updates.
+#[derive(Debug, Default)]
+pub(crate) struct LoopTimer {
+    last: Option<Instant>,
+    intervals: VecDeque<Duration>,
+    updates: u64,
+    errors: u64,
+    retries: u64,
+}
+
+impl LoopTimer {
+    pub(crate) fn tick(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last) = self.last {
+            self.intervals.push_back(now - last);
+            while self.intervals.len() > LOOP_WINDOW {
+                self.intervals.pop_front();
+            }
+        }
+
+        self.last = Some(now);
+        self.updates += 1;
+    }
+
+    pub(crate) fn error(&mut self) {
+        self.errors += 1;
+        // the time spent failing isn't an update interval
+        self.last = None;
+    }
+
+    pub(crate) fn retry(&mut self) {
+        self.retries += 1;
+    }
+
+    fn stats(&self) -> LoopStats {
+        let total: Duration = self.intervals.iter().sum();
+        let avg_interval_ms = match self.intervals.len() {
+            0 => 0.0,
+            n => total.as_secs_f64() * 1000.0 / n as f64,
+        };
+
+        LoopStats {
+            updates: self.updates,
+            avg_interval_ms,
+            max_interval_ms: self
+                .intervals
+                .iter()
+                .max()
+                .map(|d| d.as_secs_f64() * 1000.0)
+                .unwrap_or(0.0),
+            rate_hz: if avg_interval_ms > 0.0 {
+                1000.0 / avg_interval_ms
+            } else {
+                0.0
+            },
+            errors: self.errors,
+            retries: self.retries,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct LoopStats {
+    updates: u64,
+    avg_interval_ms: f64,
+    max_interval_ms: f64,
+    rate_hz: f64,
+    errors: u64,
+    retries: u64,
+}
+
+/// New fixes received by `mmrun` and bytes recorded, counted since the last
+/// `throughput` event.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Throughput {
+    pub(crate) updates: u64,
+    pub(crate) bytes_written: u64,
+}
+
+/// Emits the throughput counters as a `throughput` event once every
+/// `THROUGHPUT_INTERVAL`, resetting them each time.
+pub(crate) fn report_throughput(app: AppHandle) {
+    loop {
+        sleep(THROUGHPUT_INTERVAL);
+
+        let throughput = std::mem::take(&mut lock_state(&app).throughput);
+        app.emit("throughput", throughput).unwrap();
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct AxisStats {
+    min: f64,
+    max: f64,
+    mean: f64,
+    std: f64,
+}
+
+impl AxisStats {
+    fn from_values(values: impl Iterator<Item = f64> + Clone) -> AxisStats {
+        let (count, min, max, sum) = values.clone().fold(
+            (0usize, f64::INFINITY, f64::NEG_INFINITY, 0.0),
+            |(count, min, max, sum), v| (count + 1, min.min(v), max.max(v), sum + v),
+        );
+        let mean = sum / count as f64;
+        let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+
+        AxisStats {
+            min,
+            max,
+            mean,
+            std: variance.sqrt(),
+        }
+    }
+}
+
+/// Position statistics of one device, in display coordinates.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Stats {
+    count: usize,
+    x: AxisStats,
+    y: AxisStats,
+    z: AxisStats,
+}
+
+impl Stats {
+    fn from_points(points: &[(f64, f64, f64)]) -> Option<Stats> {
+        if points.is_empty() {
+            return None;
+        }
+
+        Some(Stats {
+            count: points.len(),
+            x: AxisStats::from_values(points.iter().map(|p| p.0)),
+            y: AxisStats::from_values(points.iter().map(|p| p.1)),
+            z: AxisStats::from_values(points.iter().map(|p| p.2)),
+        })
+    }
+}
+
+/// Returns the min, max, mean and standard deviation of a device's
+/// position, either over its trajectory buffer or over every row of the
+/// recording at `path`. Returns `None` if there are no positions.
+#[tauri::command]
+pub(crate) fn position_stats(app: AppHandle, address: u8, path: Option<String>) -> Option<Stats> {
+    let points: Vec<(f64, f64, f64)> = match path {
+        Some(path) => {
+            let samples = match read_samples(Path::new(&path)) {
+                Ok(samples) => samples,
+                Err(err) => {
+                    send_error(app, format!("failed to read recording: {:#}", err));
+                    return None;
+                }
+            };
+
+            samples.warn(&app, Path::new(&path));
+
+            let state = lock_state(&app);
+            let points = samples
+                .samples
+                .iter()
+                .filter(|s| s.address == address)
+                .map(|s| display_point(&state, s.x, s.y, s.z))
+                .collect();
+            points
+        }
+        None => {
+            let state = lock_state(&app);
+            let track = state.tracks.get(&address)?;
+            let points = track
+                .trajectory
+                .iter()
+                .map(|&(x, y, z, _)| display_point(&state, x, y, z))
+                .collect();
+            points
+        }
+    };
+
+    Stats::from_points(&points)
+}
+
+/// Position counts binned into square cells, in floorplan meters. `counts`
+/// is row-major, starting at the cell whose corner is at the origin.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct GridData {
+    origin_x: f64,
+    origin_y: f64,
+    cell_m: f64,
+    cols: usize,
+    rows: usize,
+    counts: Vec<u32>,
+}
+
+impl GridData {
+    /// Bins `points` into cells of `cell_m` meters, failing if the grid
+    /// would have more than `MAX_GRID_CELLS` cells.
+    fn from_points(points: &[(f64, f64)], cell_m: f64) -> Result<GridData, Error> {
+        let empty = GridData {
+            cell_m,
+            ..Default::default()
+        };
+
+        let Some(bounds) = points.iter().fold(None, |bounds, &(x, y)| match bounds {
+            None => Some((x, y, x, y)),
+            Some((min_x, min_y, max_x, max_y)) => {
+                Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)))
+            }
+        }) else {
+            return Ok(empty);
+        };
+
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let origin_x = (min_x / cell_m).floor() * cell_m;
+        let origin_y = (min_y / cell_m).floor() * cell_m;
+        let cols = ((max_x - origin_x) / cell_m).floor() + 1.0;
+        let rows = ((max_y - origin_y) / cell_m).floor() + 1.0;
+
+        // a stray far-off position or a tiny cell size would otherwise
+        // allocate without bound
+        let cells = cols * rows;
+        if cells.is_nan() || cells > MAX_GRID_CELLS {
+            return Err(Error::msg(format!(
+                "{} x {} cells exceed the limit of {}, try a larger cell size",
+                cols, rows, MAX_GRID_CELLS
+            )));
+        }
+        let (cols, rows) = (cols as usize, rows as usize);
+
+        let mut counts = vec![0; cols * rows];
+        for &(x, y) in points {
+            let col = (((x - origin_x) / cell_m) as usize).min(cols - 1);
+            let row = (((y - origin_y) / cell_m) as usize).min(rows - 1);
+            counts[row * cols + col] += 1;
+        }
+
+        Ok(GridData {
+            origin_x,
+            origin_y,
+            cell_m,
+            cols,
+            rows,
+            counts,
+        })
+    }
+}
+
+/// Bins every position in the recording at `path` into cells of `cell_m`
+/// meters, for drawing a heatmap over the floorplan.
+#[tauri::command]
+pub(crate) fn occupancy_grid(app: AppHandle, path: String, cell_m: f64) -> GridData {
+    if !cell_m.is_finite() || cell_m <= 0.0 {
+        send_error(app, format!("invalid cell size: {}", cell_m));
+        return GridData::default();
+    }
+
+    let samples = match read_samples(Path::new(&path)) {
+        Ok(samples) => samples,
+        Err(err) => {
+            send_error(app, format!("failed to read recording: {:#}", err));
+            return GridData::default();
+        }
+    };
+
+    samples.warn(&app, Path::new(&path));
+
+    let state = lock_state(&app);
+    let points: Vec<(f64, f64)> = samples
+        .samples
+        .iter()
+        .map(|s| match &state.plan {
+            Some(plan) => transform_point(plan, s.x, s.y),
+            None => (s.x, s.y),
+        })
+        .collect();
+
+    GridData::from_points(&points, cell_m).unwrap_or_else(|err| {
+        send_error(
+            app.clone(),
+            format!("failed to build occupancy grid: {:#}", err),
+        );
+        GridData::default()
+    })
+}
+
+/// Distance and speed of one device over a recording, in display units and
+/// seconds.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TrackMetrics {
+    path_length: f64,
+    duration_ms: u64,
+    avg_speed: f64,
+    max_speed: f64,
+}
+
+impl TrackMetrics {
+    fn from_samples<'a>(samples: impl Iterator<Item = &'a Sample>, scale: f64) -> TrackMetrics {
+        let mut metrics = TrackMetrics::default();
+        let mut start_t = None;
+        let mut prev: Option<&Sample> = None;
+
+        for sample in samples {
+            let first_t = *start_t.get_or_insert(sample.t);
+            metrics.duration_ms = sample.t.saturating_sub(first_t);
+
+            if let Some(prev) = prev.replace(sample) {
+                // a zero quality fix isn't a real position
+                if prev.q == 0 || sample.q == 0 {
+                    continue;
+                }
+
+                let distance = ((sample.x - prev.x).powi(2)
+                    + (sample.y - prev.y).powi(2)
+                    + (sample.z - prev.z).powi(2))
+                .sqrt()
+                    * scale;
+                metrics.path_length += distance;
+
+                let dt_s = sample.t.saturating_sub(prev.t) as f64 / 1000.0;
+                if dt_s > 0.0 {
+                    metrics.max_speed = metrics.max_speed.max(distance / dt_s);
+                }
+            }
+        }
+
+        if metrics.duration_ms > 0 {
+            metrics.avg_speed = metrics.path_length / (metrics.duration_ms as f64 / 1000.0);
+        }
+
+        metrics
+    }
+}
+
+/// Returns how far a device traveled in the recording at `path`, and how
+/// fast, skipping segments with a zero quality end.
+#[tauri::command]
+pub(crate) fn track_metrics(app: AppHandle, path: String, address: u8) -> TrackMetrics {
+    let samples = match read_samples(Path::new(&path)) {
+        Ok(samples) => samples,
+        Err(err) => {
+            send_error(app, format!("failed to read recording: {:#}", err));
+            return TrackMetrics::default();
+        }
+    };
+
+    samples.warn(&app, Path::new(&path));
+
+    let mut track: Vec<&Sample> = samples
+        .samples
+        .iter()
+        .filter(|s| s.address == address)
+        .collect();
+    track.sort_by_key(|s| s.t);
+
+    let scale = lock_state(&app).units.per_meter();
+
+    TrackMetrics::from_samples(track.into_iter(), scale)
+}
+
+/// Returns the achieved update rate of the modem loop since it was started,
+/// along with how often updating failed and reconnecting was retried.
+#[tauri::command]
+pub(crate) fn loop_stats(app: AppHandle) -> LoopStats {
+    lock_state(&app).loop_timer.stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_counts_points_per_cell() {
+        let grid = GridData::from_points(&[(0.1, 0.1), (0.2, 0.3), (1.5, 0.1)], 1.0).unwrap();
+
+        assert_eq!((grid.cols, grid.rows), (2, 1));
+        assert_eq!(grid.counts, [2, 1]);
+    }
+
+    #[test]
+    fn oversized_grid_is_refused() {
+        assert!(GridData::from_points(&[(0.0, 0.0), (1000.0, 1000.0)], 0.01).is_err());
+        assert!(GridData::from_points(&[(0.0, 0.0), (f64::INFINITY, 0.0)], 1.0).is_err());
+    }
+}
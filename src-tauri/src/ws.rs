@@ -0,0 +1,170 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::Duration,
+};
+
+use tauri::AppHandle;
+use tungstenite::{Message, WebSocket};
+
+use crate::{lock_state, send_error, send_log, send_warn};
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+const CLIENT_QUEUE: usize = 64;
+
+/// A localhost WebSocket server broadcasting device updates to every
+/// connected client.
+#[derive(Debug)]
+pub(crate) struct WsServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    clients: Arc<Mutex<Vec<SyncSender<String>>>>,
+    accept_thread: JoinHandle<()>,
+}
+
+impl WsServer {
+    /// Queues a message for every client without waiting on the sockets,
+    /// since this is called with the app state locked.
+    pub(crate) fn broadcast(&self, json: &str) {
+        let mut clients = self.clients.lock().unwrap();
+
+        // drop clients that have gone away, and skip a message for those
+        // too far behind to take it
+        clients.retain(|client| match client.try_send(json.to_string()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+
+    /// Stops accepting clients and waits for the listener to be closed, so
+    /// the port can be bound again. This waits on a handshake in progress, so
+    /// it must not be called with the app state locked.
+    fn shutdown(self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // closing the queues makes each writer close its client
+        self.clients.lock().unwrap().clear();
+
+        let _ = self.accept_thread.join();
+    }
+}
+
+/// Sends queued messages to a client until it goes away or its queue is
+/// closed.
+fn write_client(mut client: WebSocket<TcpStream>, messages: mpsc::Receiver<String>) {
+    for json in messages {
+        if client.send(Message::text(json)).is_err() {
+            return;
+        }
+    }
+
+    let _ = client.close(None);
+    let _ = client.flush();
+}
+
+fn accept_clients(
+    app: AppHandle,
+    listener: TcpListener,
+    stop: Arc<AtomicBool>,
+    clients: Arc<Mutex<Vec<SyncSender<String>>>>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                sleep(ACCEPT_INTERVAL);
+                continue;
+            }
+            Err(err) => {
+                send_error(app, format!("websocket server stopped: {}", err));
+                return;
+            }
+        };
+
+        // a client that never finishes the handshake must not hold up the
+        // others
+        if stream.set_nonblocking(false).is_err()
+            || stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err()
+        {
+            continue;
+        }
+
+        match tungstenite::accept(stream) {
+            Ok(client) => {
+                let (sender, messages) = mpsc::sync_channel(CLIENT_QUEUE);
+                thread::spawn(move || write_client(client, messages));
+                clients.lock().unwrap().push(sender);
+            }
+            Err(err) => send_warn(app.clone(), format!("websocket handshake failed: {}", err)),
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn start_ws_server(app: AppHandle, port: u16) {
+    let server = lock_state(&app).ws_server.take();
+    if let Some(server) = server {
+        server.shutdown();
+    }
+
+    let mut state = lock_state(&app);
+
+    let listener = match TcpListener::bind(("127.0.0.1", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    {
+        Ok(listener) => listener,
+        Err(err) => {
+            send_error(
+                app.clone(),
+                format!("failed to start websocket server on port {}: {}", port, err),
+            );
+            return;
+        }
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let clients = Arc::new(Mutex::new(Vec::new()));
+
+    let accept_thread = thread::spawn({
+        let app = app.clone();
+        let stop = stop.clone();
+        let clients = clients.clone();
+        move || accept_clients(app, listener, stop, clients)
+    });
+
+    let server = WsServer {
+        port,
+        stop,
+        clients,
+        accept_thread,
+    };
+
+    send_log(
+        app.clone(),
+        format!("websocket server listening on port {}", server.port),
+    );
+    state.ws_server = Some(server);
+}
+
+#[tauri::command]
+pub(crate) fn stop_ws_server(app: AppHandle) {
+    let server = lock_state(&app).ws_server.take();
+    if let Some(server) = server {
+        server.shutdown();
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2025 wyzdwdz <wyzdwdz@gmail.com>
+//
+// Licensed under the MIT license <LICENSE or https://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to
+// those terms.
+
+use tauri::{AppHandle, Emitter};
+
+use crate::{is_shown, lock_state, AppState};
+
+/// The area of a zone, in meters in the Marvelmind frame.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Shape {
+    Rect {
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    },
+    /// A simple polygon through `points` in order, closed back to the first.
+    Polygon { points: Vec<(f64, f64)> },
+}
+
+/// A named area, given either by its bounds or by the vertices of a polygon.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Zone {
+    name: String,
+    #[serde(flatten)]
+    shape: Shape,
+}
+
+impl Zone {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        match &self.shape {
+            Shape::Rect {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            } => (*min_x..=*max_x).contains(&x) && (*min_y..=*max_y).contains(&y),
+            Shape::Polygon { points } => polygon_contains(points, x, y),
+        }
+    }
+}
+
+/// Even-odd test counting the polygon edges a ray from the point crosses.
+/// Points on an edge count as inside, as on the bounds of a rectangle.
+fn polygon_contains(points: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut is_inside = false;
+
+    for (index, &(xi, yi)) in points.iter().enumerate() {
+        let (xj, yj) = points[(index + points.len() - 1) % points.len()];
+
+        // the crossing count alone puts only some of the boundary inside
+        let is_on_edge = (xj - xi) * (y - yi) == (yj - yi) * (x - xi)
+            && (xi.min(xj)..=xi.max(xj)).contains(&x)
+            && (yi.min(yj)..=yi.max(yj)).contains(&y);
+        if is_on_edge {
+            return true;
+        }
+
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            is_inside = !is_inside;
+        }
+    }
+
+    is_inside
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct ZoneEvent {
+    address: u8,
+    zone: String,
+}
+
+/// Emits `zone-enter` and `zone-exit` for hedgehogs crossing zone
+/// boundaries since the last check.
+pub(crate) fn check_zones(app: &AppHandle, state: &mut AppState) {
+    for device in state.devices.iter().filter(|d| d.is_hedge) {
+        if !is_shown(state, device.address) {
+            continue;
+        }
+
+        let track = state.tracks.entry(device.address).or_default();
+
+        for zone in &state.zones {
+            let is_inside = zone.contains(device.x, device.y);
+            let was_inside = track.zones.contains(&zone.name);
+
+            let event = match (was_inside, is_inside) {
+                (false, true) => {
+                    track.zones.insert(zone.name.clone());
+                    "zone-enter"
+                }
+                (true, false) => {
+                    track.zones.remove(&zone.name);
+                    "zone-exit"
+                }
+                _ => continue,
+            };
+
+            app.emit(
+                event,
+                ZoneEvent {
+                    address: device.address,
+                    zone: zone.name.clone(),
+                },
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Replaces the zones checked while running. Devices already inside a new
+/// zone get a `zone-enter` on the next update.
+#[tauri::command]
+pub(crate) fn set_zones(app: AppHandle, zones: Vec<Zone>) {
+    let mut state = lock_state(&app);
+
+    state.zones = zones;
+    for track in state.tracks.values_mut() {
+        track.zones.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [(f64, f64); 4] = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+
+    // a U opening upwards, with a notch between x = 2 and x = 4 above y = 2
+    const U: [(f64, f64); 8] = [
+        (0.0, 0.0),
+        (6.0, 0.0),
+        (6.0, 6.0),
+        (4.0, 6.0),
+        (4.0, 2.0),
+        (2.0, 2.0),
+        (2.0, 6.0),
+        (0.0, 6.0),
+    ];
+
+    #[test]
+    fn polygon_contains_inside_points() {
+        assert!(polygon_contains(&SQUARE, 2.0, 2.0));
+        assert!(polygon_contains(&SQUARE, 0.5, 3.5));
+    }
+
+    #[test]
+    fn polygon_excludes_outside_points() {
+        assert!(!polygon_contains(&SQUARE, 5.0, 2.0));
+        assert!(!polygon_contains(&SQUARE, -1.0, -1.0));
+        assert!(!polygon_contains(&SQUARE, 2.0, 4.5));
+    }
+
+    #[test]
+    fn polygon_boundary_is_inside() {
+        for &(x, y) in &SQUARE {
+            assert!(polygon_contains(&SQUARE, x, y), "vertex ({}, {})", x, y);
+        }
+        assert!(polygon_contains(&SQUARE, 2.0, 4.0));
+        assert!(polygon_contains(&SQUARE, 4.0, 1.0));
+    }
+
+    #[test]
+    fn concave_polygon_excludes_its_notch() {
+        assert!(!polygon_contains(&U, 3.0, 4.0));
+        assert!(polygon_contains(&U, 1.0, 4.0));
+        assert!(polygon_contains(&U, 5.0, 5.0));
+        assert!(polygon_contains(&U, 3.0, 1.0));
+        assert!(polygon_contains(&U, 4.0, 4.0));
+    }
+}
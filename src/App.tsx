@@ -29,20 +29,46 @@ import "@fontsource/roboto/500.css";
 import "@fontsource/roboto/700.css";
 import "./App.css";
 
+interface Floor {
+  file: string;
+  data: Uint8Array;
+  ext: string;
+}
+
 interface Plan {
   x: number;
   y: number;
   scale_pixels_per_m: number;
-  data: Uint8Array;
-  ext: string;
+  rotation_deg: number;
+  floors: Floor[];
 }
 
 interface Device {
   address: number;
+  name: string | null;
+  color: string | null;
+  dtype: string;
   is_hedge: boolean;
   x: number;
   y: number;
+  z: number;
+  vx: number;
+  vy: number;
+  vz: number;
   q: number;
+  last_update_ms: number;
+}
+
+interface ParsedMap {
+  devices: Device[];
+  plan: Plan | null;
+  error: string | null;
+}
+
+interface LogMessage {
+  level: "info" | "warn" | "error";
+  message: string;
+  timestamp: number;
 }
 
 const RedSwitch = styled(Switch)(({ theme }) => ({
@@ -62,11 +88,13 @@ function SensorMarker({
   y,
   q,
   is_hedge,
+  color,
 }: {
   x: number;
   y: number;
   q: number;
   is_hedge: boolean;
+  color: string | null;
 }) {
   const text = "x: " + x.toFixed(2) + "\ny: " + y.toFixed(2) + "\nq: " + q;
   return (
@@ -80,7 +108,7 @@ function SensorMarker({
           text={text}
         />
       )}
-      <Circle x={0} y={0} radius={0.1} fill={is_hedge ? "red" : "blue"} />
+      <Circle x={0} y={0} radius={0.1} fill={color ?? (is_hedge ? "red" : "blue")} />
     </Group>
   );
 }
@@ -192,8 +220,17 @@ function FloorPlan({
   );
 }
 
-function VisualStage({ devices, plan }: { devices: Device[]; plan: Plan }) {
+function VisualStage({
+  devices,
+  plan,
+  floor,
+}: {
+  devices: Device[];
+  plan: Plan;
+  floor: number;
+}) {
   const refStage = useRef<Konva.Stage>(null);
+  const currentFloor = plan.floors[floor] ?? plan.floors[0];
 
   useEffect(() => {
     const stage = refStage.current;
@@ -261,11 +298,11 @@ function VisualStage({ devices, plan }: { devices: Device[]; plan: Plan }) {
     >
       <Layer>
         <FloorPlan
-          x={plan.x}
-          y={-plan.y}
+          x={0}
+          y={0}
           scale_pixels_per_m={plan.scale_pixels_per_m}
-          data={plan.data}
-          ext={plan.ext}
+          data={currentFloor.data}
+          ext={currentFloor.ext}
         />
         {devices.map((device) => (
           <SensorMarker
@@ -283,8 +320,15 @@ export default function App() {
   const [plan, setPlan] = useState<Plan | null>(null);
 
   useEffect(() => {
-    const unlisten = listen<string>("log-message", (event) => {
-      console.log(`Log: ${event.payload}`);
+    const unlisten = listen<LogMessage>("log-message", (event) => {
+      const { level, message } = event.payload;
+      if (level === "error") {
+        console.error(`Log: ${message}`);
+      } else if (level === "warn") {
+        console.warn(`Log: ${message}`);
+      } else {
+        console.log(`Log: ${message}`);
+      }
     });
 
     return () => {
@@ -302,9 +346,12 @@ export default function App() {
         return;
       }
 
-      invoke<[Device[], Plan | null]>("parse_map", {
+      invoke<ParsedMap>("parse_map", {
         path: event.payload.paths[0],
-      }).then(([tr_devices, tr_plan]) => {
+      }).then(({ devices: tr_devices, plan: tr_plan, error }) => {
+        if (error) {
+          console.error(error);
+        }
         setDevices(tr_devices);
         setPlan(tr_plan);
       });
@@ -371,7 +418,7 @@ export default function App() {
   return (
     <>
       {devices.length > 0 && plan && (
-        <VisualStage devices={devices} plan={plan} />
+        <VisualStage devices={devices} plan={plan} floor={0} />
       )}
       <div
         style={{